@@ -0,0 +1,55 @@
+//! Perft node rate plus a generation-only loop that isolates move list cost.
+//!
+//! Perft counts its last ply from the move list, so most of its time goes to
+//! generation and the move list shows up in the node rate. Run with
+//! `cargo run --release --example perft_bench`, several times, since single
+//! runs vary widely.
+
+use std::time::Instant;
+
+use chess_engine::board::board::Board;
+use chess_engine::movegen::movegen::generate_legal_moves;
+use chess_engine::movegen::perft::perft;
+
+fn main() {
+    let positions = [
+        ("startpos", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 5),
+        ("kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 4),
+        ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 6),
+    ];
+
+    let mut total_nodes = 0;
+    let start = Instant::now();
+
+    for (name, fen, depth) in positions {
+        let mut board = Board::from_fen(fen).unwrap();
+        let t = Instant::now();
+        let nodes = perft(&mut board, depth);
+        let secs = t.elapsed().as_secs_f64();
+        total_nodes += nodes;
+        println!(
+            "{:<10} depth {}: {:>10} nodes in {:.3}s ({:.0} nps)",
+            name, depth, nodes, secs, nodes as f64 / secs
+        );
+    }
+
+    let secs = start.elapsed().as_secs_f64();
+    println!("\nTotal: {} nodes in {:.3}s ({:.0} nps)", total_nodes, secs, total_nodes as f64 / secs);
+
+    // Generation alone, without make/unmake, to isolate the move list cost
+    let boards: Vec<Board> = positions.iter().map(|(_, fen, _)| Board::from_fen(fen).unwrap()).collect();
+    let iterations = 1_000_000;
+    let mut generated = 0;
+    let t = Instant::now();
+    for _ in 0..iterations {
+        for board in &boards {
+            generated += generate_legal_moves(board).len();
+        }
+    }
+    let secs = t.elapsed().as_secs_f64();
+    let calls = iterations * boards.len();
+    println!(
+        "Movegen:   {} calls, {} moves in {:.3}s ({:.0} calls/s)",
+        calls, generated, secs, calls as f64 / secs
+    );
+}
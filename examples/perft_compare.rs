@@ -2,31 +2,7 @@ use chess_engine::board::board::Board;
 use chess_engine::movegen::perft::perft;
 use chess_engine::movegen::movegen::generate_legal_moves;
 
-fn square_name(sq: u8) -> String {
-    let file = (sq % 8) as u8 + b'a';
-    let rank = (sq / 8) + 1;
-    format!("{}{}", file as char, rank)
-}
-
-fn move_to_string(mv: &chess_engine::board::r#move::Move) -> String {
-    let mut s = format!("{}{}", square_name(mv.from), square_name(mv.to));
-    if let Some(p) = mv.promotion {
-        let c = match p {
-            1 => 'n',
-            2 => 'b',
-            3 => 'r',
-            4 => 'q',
-            _ => '?',
-        };
-        s.push(c);
-    }
-    s
-}
-
 fn main() {
-    // Initialize attack tables
-    chess_engine::movegen::init();
-    
     // Known correct perft(5) divide from starting position:
     let expected: Vec<(&str, u64)> = vec![
         ("a2a3", 181046),
@@ -52,7 +28,7 @@ fn main() {
     ];
     
     let mut board = Board::startpos();
-    let moves = generate_legal_moves(&mut board);
+    let moves = generate_legal_moves(&board);
     
     println!("Perft divide comparison at depth 4 from starting position:\n");
     
@@ -60,7 +36,7 @@ fn main() {
     
     for (mv_str, exp) in &expected {
         // Find the move
-        let mv = moves.iter().find(|m| move_to_string(m) == *mv_str);
+        let mv = moves.iter().find(|m| m.to_uci() == *mv_str);
         if let Some(mv) = mv {
             let undo = board.make_move(*mv);
             let count = perft(&mut board, 4);
@@ -1,3 +1,5 @@
+use crate::board::piece::Color;
+
 pub type Bitboard = u64;
 
 #[inline(always)]
@@ -15,6 +17,61 @@ pub const fn file_of(square: u8) -> u8 {
     square % 8
 }
 
+/// Chebyshev distance: the number of king steps between two squares.
+#[inline]
+pub fn distance(a: u8, b: u8) -> u8 {
+    file_of(a).abs_diff(file_of(b)).max(rank_of(a).abs_diff(rank_of(b)))
+}
+
+/// Rank counted from `color`'s own side, 0 for its back rank.
+#[inline]
+pub const fn relative_rank(color: Color, square: u8) -> u8 {
+    match color {
+        Color::White => rank_of(square),
+        Color::Black => 7 - rank_of(square),
+    }
+}
+
+#[inline]
+pub const fn file_mask(file: u8) -> Bitboard {
+    FILE_A << file
+}
+
+/// The files either side of `file`.
+#[inline]
+pub const fn adjacent_files(file: u8) -> Bitboard {
+    let mask = file_mask(file);
+    ((mask << 1) & !FILE_A) | ((mask >> 1) & !FILE_H)
+}
+
+/// Every rank strictly in front of `square` from `color`'s point of view.
+#[inline]
+pub const fn forward_ranks(color: Color, square: u8) -> Bitboard {
+    let rank = rank_of(square);
+    match color {
+        Color::White if rank == 7 => 0,
+        Color::White => !0 << ((rank + 1) * 8),
+        Color::Black => (1 << (rank * 8)) - 1,
+    }
+}
+
+/// Squares attacked by any of `color`'s pawns in `pawns`.
+#[inline]
+pub const fn pawn_attacks(color: Color, pawns: Bitboard) -> Bitboard {
+    match color {
+        Color::White => ((pawns << 7) & !FILE_H) | ((pawns << 9) & !FILE_A),
+        Color::Black => ((pawns >> 7) & !FILE_A) | ((pawns >> 9) & !FILE_H),
+    }
+}
+
+/// Remove the lowest set square from `bb` and return it.
+#[inline]
+pub fn pop_lsb(bb: &mut Bitboard) -> u8 {
+    let sq = bb.trailing_zeros() as u8;
+    *bb &= *bb - 1;
+    sq
+}
+
 
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -60,4 +117,17 @@ mod tests {
         assert_eq!(file_of(63), 7); // H file
         assert_eq!(rank_of(63), 7); // rank 8
     }
+
+    #[test]
+    fn masks() {
+        assert_eq!(adjacent_files(0), file_mask(1));
+        assert_eq!(adjacent_files(4), file_mask(3) | file_mask(5));
+        assert_eq!(forward_ranks(Color::White, Square::E2 as u8), !(RANK_1 | RANK_2));
+        assert_eq!(forward_ranks(Color::Black, Square::E2 as u8), RANK_1);
+        assert_eq!(forward_ranks(Color::White, Square::E8 as u8), 0);
+        assert_eq!(pawn_attacks(Color::White, bb(Square::A2 as u8)), bb(Square::B3 as u8));
+        assert_eq!(pawn_attacks(Color::Black, bb(Square::H7 as u8)), bb(Square::G6 as u8));
+        assert_eq!(distance(Square::A1 as u8, Square::H8 as u8), 7);
+        assert_eq!(distance(Square::E4 as u8, Square::F6 as u8), 2);
+    }
 }
@@ -2,11 +2,15 @@ use crate::board::bitboard::*;
 use crate::board::piece::{Piece, Color, index_to_piece};
 use crate::board::undo::Undo;
 use crate::board::r#move::Move;
+use crate::board::zobrist;
+use crate::eval::state::EvalState;
 use crate::movegen::attacks::{
     rook_attacks,
     bishop_attacks,
     knight_attacks,
     king_attacks,
+    white_pawn_attacks,
+    black_pawn_attacks,
 };
 
 
@@ -17,6 +21,11 @@ pub struct Board {
     pub occupied: Bitboard,
     pub castling_rights: u8,
     pub en_passant_square: Option<u8>,
+    pub halfmove_clock: u16,
+    pub fullmove_number: u16,
+    pub hash: u64,
+    pub history: Vec<u64>, // hashes of earlier positions, oldest first
+    pub eval: EvalState,
 }
 
 impl Piece {
@@ -43,6 +52,26 @@ impl Color {
     }
 }
 
+/// Rook squares (from, to) for the castle whose king lands on `king_to`.
+#[inline]
+fn castling_rook_squares(king_to: u8) -> (u8, u8) {
+    match king_to {
+        6 => (7, 5),    // e1g1: h1 -> f1
+        2 => (0, 3),    // e1c1: a1 -> d1
+        62 => (63, 61), // e8g8: h8 -> f8
+        _ => (56, 59),  // e8c8: a8 -> d8
+    }
+}
+
+/// Square of the pawn taken by an en passant capture landing on `to`.
+#[inline]
+fn en_passant_victim(capturer: Color, to: u8) -> u8 {
+    match capturer {
+        Color::White => to - 8,
+        Color::Black => to + 8,
+    }
+}
+
 impl Board {
     pub fn empty() -> Self {
         Board {
@@ -51,6 +80,11 @@ impl Board {
             occupied: 0,
             castling_rights: 0,
             en_passant_square: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+            history: Vec::new(),
+            eval: EvalState::default(),
         }
     }
 
@@ -82,10 +116,96 @@ impl Board {
         b.pieces[1][5] = bb(60);
 
         b.castling_rights = CASTLE_WHITE_KING | CASTLE_WHITE_QUEEN | CASTLE_BLACK_KING | CASTLE_BLACK_QUEEN;
-        b.recompute_occupancy();
+        b.refresh();
         b
     }
 
+    /// Recompute occupancy, the hash and the evaluation state after editing
+    /// pieces or state fields directly.
+    pub fn refresh(&mut self) {
+        self.recompute_occupancy();
+        self.hash = self.compute_hash();
+        self.eval = EvalState::compute(self);
+    }
+
+    /// Compute the Zobrist key from scratch.
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for color in [Color::White, Color::Black] {
+            for p in 0..6 {
+                let mut pieces = self.pieces[color.index()][p];
+                while pieces != 0 {
+                    let sq = pieces.trailing_zeros() as u8;
+                    pieces &= pieces - 1;
+                    hash ^= zobrist::piece_key(color, index_to_piece(p), sq);
+                }
+            }
+        }
+
+        if self.side_to_move == Color::Black {
+            hash ^= zobrist::side_key();
+        }
+        hash ^= zobrist::castling_key(self.castling_rights);
+        if let Some(ep) = self.en_passant_square {
+            hash ^= self.en_passant_hash(ep, self.side_to_move);
+        }
+
+        hash
+    }
+
+    /// The en passant file only enters the hash when `side` can actually
+    /// capture, so positions that differ in nothing else still repeat.
+    #[inline]
+    fn en_passant_hash(&self, ep: u8, side: Color) -> u64 {
+        let ep_bb = bb(ep);
+        let pawns = self.pieces[side.index()][Piece::Pawn.index()];
+        let attackers = match side {
+            Color::White => ((ep_bb >> 7) & !FILE_A) | ((ep_bb >> 9) & !FILE_H),
+            Color::Black => ((ep_bb << 7) & !FILE_H) | ((ep_bb << 9) & !FILE_A),
+        };
+
+        if pawns & attackers != 0 {
+            zobrist::en_passant_key(file_of(ep))
+        } else {
+            0
+        }
+    }
+
+    /// True if the current position occurred at least `count` times before.
+    /// Only positions since the last irreversible move can repeat.
+    pub fn is_repetition(&self, count: usize) -> bool {
+        let lookback = (self.halfmove_clock as usize).min(self.history.len());
+
+        // The previous entry has the other side to move, so compare every second one
+        self.history
+            .iter()
+            .rev()
+            .take(lookback)
+            .skip(1)
+            .step_by(2)
+            .filter(|&&h| h == self.hash)
+            .count()
+            >= count
+    }
+
+    /// True if the current position already occurred at history index
+    /// `start` or later, since the last irreversible move.
+    pub fn is_repetition_since(&self, start: usize) -> bool {
+        let len = self.history.len();
+        let lookback = (self.halfmove_clock as usize).min(len);
+        (2..=lookback)
+            .step_by(2)
+            .map(|back| len - back)
+            .take_while(|&index| index >= start)
+            .any(|index| self.history[index] == self.hash)
+    }
+
+    /// Draw by the fifty-move rule or threefold repetition.
+    pub fn is_draw(&self) -> bool {
+        self.halfmove_clock >= 100 || self.is_repetition(2)
+    }
+
     #[inline]
     pub fn recompute_occupancy(&mut self) {
         self.occupied = 0;
@@ -126,52 +246,73 @@ impl Board {
     }
 
     pub fn make_move(&mut self, mv: Move) -> Undo {
-        let from_mask = bb(mv.from);
-        let to_mask = bb(mv.to);
+        let (from, to) = (mv.from(), mv.to());
+        let from_mask = bb(from);
+        let to_mask = bb(to);
 
         let color = self.side_to_move;
         let enemy = color.opposite();
 
-        let mut moved_piece = None;
+        let prev_hash = self.hash;
+        let prev_eval = self.eval;
+        self.history.push(prev_hash);
+        if let Some(ep) = self.en_passant_square {
+            self.hash ^= self.en_passant_hash(ep, color);
+        }
 
-        // Find moving piece
-        for p in 0..6 {
-            if self.pieces[color.index()][p] & from_mask != 0 {
-                self.pieces[color.index()][p] ^= from_mask;
-                self.pieces[color.index()][p] |= to_mask;
-                moved_piece = Some(p);
-                break;
+        // Handle capture. En passant takes the pawn behind the target square.
+        let mut captured = None;
+        if mv.is_capture() {
+            let captured_sq = if mv.is_en_passant() {
+                en_passant_victim(color, to)
+            } else {
+                to
+            };
+            for p in 0..6 {
+                if self.pieces[enemy.index()][p] & bb(captured_sq) != 0 {
+                    self.pieces[enemy.index()][p] ^= bb(captured_sq);
+                    self.hash ^= zobrist::piece_key(enemy, index_to_piece(p), captured_sq);
+                    self.eval.remove(enemy, index_to_piece(p), captured_sq);
+                    captured = Some((enemy, index_to_piece(p)));
+                    break;
+                }
             }
+            debug_assert!(captured.is_some());
         }
 
-        debug_assert!(moved_piece.is_some());
-
-        // Handle capture
-        let mut captured = None;
+        // Find moving piece
+        let mut moved_piece = None;
         for p in 0..6 {
-            if self.pieces[enemy.index()][p] & to_mask != 0 {
-                self.pieces[enemy.index()][p] ^= to_mask;
-                captured = Some((enemy, index_to_piece(p)));
+            if self.pieces[color.index()][p] & from_mask != 0 {
+                self.pieces[color.index()][p] ^= from_mask | to_mask;
+                self.hash ^= zobrist::piece_key(color, index_to_piece(p), from)
+                    ^ zobrist::piece_key(color, index_to_piece(p), to);
+                self.eval.move_piece(color, index_to_piece(p), from, to);
+                moved_piece = Some(index_to_piece(p));
                 break;
             }
         }
 
-        // Handle en passant capture
-        if moved_piece == Some(Piece::Pawn.index()) && Some(mv.to) == self.en_passant_square {
-            // Remove the captured pawn (it's not on the 'to' square)
-            let captured_pawn_sq = match color {
-                Color::White => mv.to - 8,
-                Color::Black => mv.to + 8,
-            };
-            self.pieces[enemy.index()][Piece::Pawn.index()] ^= bb(captured_pawn_sq);
-            captured = Some((enemy, Piece::Pawn));
-        }
+        debug_assert!(moved_piece.is_some());
 
         // Handle promotion
-        if let Some(promo_piece) = mv.promotion {
+        if let Some(promo_piece) = mv.promotion() {
             // Remove the pawn from destination and add the promoted piece
             self.pieces[color.index()][Piece::Pawn.index()] ^= to_mask;
-            self.pieces[color.index()][promo_piece as usize] |= to_mask;
+            self.pieces[color.index()][promo_piece.index()] |= to_mask;
+            self.hash ^= zobrist::piece_key(color, Piece::Pawn, to)
+                ^ zobrist::piece_key(color, promo_piece, to);
+            self.eval.remove(color, Piece::Pawn, to);
+            self.eval.add(color, promo_piece, to);
+        }
+
+        // Handle castling rook movement
+        if mv.is_castle() {
+            let (rook_from, rook_to) = castling_rook_squares(to);
+            self.pieces[color.index()][Piece::Rook.index()] ^= bb(rook_from) | bb(rook_to);
+            self.hash ^= zobrist::piece_key(color, Piece::Rook, rook_from)
+                ^ zobrist::piece_key(color, Piece::Rook, rook_to);
+            self.eval.move_piece(color, Piece::Rook, rook_from, rook_to);
         }
 
         // Update occupancy
@@ -182,48 +323,39 @@ impl Board {
             side_to_move: self.side_to_move,
             castling_rights: self.castling_rights,
             en_passant_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            hash: prev_hash,
+            eval: prev_eval,
         };
 
-        // Update en passant square
-        self.en_passant_square = None;
-        if moved_piece == Some(Piece::Pawn.index()) && mv.from.abs_diff(mv.to) == 16 {
-            // Pawn moved two squares, set en passant square
-            self.en_passant_square = Some((mv.from + mv.to) / 2);
+        // Pawn moves and captures reset the fifty-move counter
+        if moved_piece == Some(Piece::Pawn) || captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if color == Color::Black {
+            self.fullmove_number += 1;
         }
 
-        // Handle castling rook movement
-        if moved_piece == Some(Piece::King.index()) && mv.from.abs_diff(mv.to) == 2 {
-            match (color, mv.to) {
-                (Color::White, 6) => { // Kingside castle (e1g1)
-                    self.pieces[0][Piece::Rook.index()] ^= bb(7); // Remove from h1
-                    self.pieces[0][Piece::Rook.index()] |= bb(5); // Add to f1
-                },
-                (Color::White, 2) => { // Queenside castle (e1c1)
-                    self.pieces[0][Piece::Rook.index()] ^= bb(0); // Remove from a1
-                    self.pieces[0][Piece::Rook.index()] |= bb(3); // Add to d1
-                },
-                (Color::Black, 62) => { // Kingside castle (e8g8)
-                    self.pieces[1][Piece::Rook.index()] ^= bb(63); // Remove from h8
-                    self.pieces[1][Piece::Rook.index()] |= bb(61); // Add to f8
-                },
-                (Color::Black, 58) => { // Queenside castle (e8c8)
-                    self.pieces[1][Piece::Rook.index()] ^= bb(56); // Remove from a8
-                    self.pieces[1][Piece::Rook.index()] |= bb(59); // Add to d8
-                },
-                _ => {}
-            }
-            self.recompute_occupancy();
+        // Update en passant square
+        self.en_passant_square = None;
+        if mv.is_double_push() {
+            let ep = (from + to) / 2;
+            self.en_passant_square = Some(ep);
+            self.hash ^= self.en_passant_hash(ep, enemy);
         }
 
         // Update castling rights
-        if moved_piece == Some(Piece::King.index()) {
+        self.hash ^= zobrist::castling_key(self.castling_rights);
+        if moved_piece == Some(Piece::King) {
             if color == Color::White {
                 self.castling_rights &= !(CASTLE_WHITE_KING | CASTLE_WHITE_QUEEN);
             } else {
                 self.castling_rights &= !(CASTLE_BLACK_KING | CASTLE_BLACK_QUEEN);
             }
-        } else if moved_piece == Some(Piece::Rook.index()) {
-            match mv.from {
+        } else if moved_piece == Some(Piece::Rook) {
+            match from {
                 0 => self.castling_rights &= !CASTLE_WHITE_QUEEN,
                 7 => self.castling_rights &= !CASTLE_WHITE_KING,
                 56 => self.castling_rights &= !CASTLE_BLACK_QUEEN,
@@ -233,7 +365,7 @@ impl Board {
         }
         // If a rook is captured, remove castling rights
         if captured.is_some() {
-            match mv.to {
+            match to {
                 0 => self.castling_rights &= !CASTLE_WHITE_QUEEN,
                 7 => self.castling_rights &= !CASTLE_WHITE_KING,
                 56 => self.castling_rights &= !CASTLE_BLACK_QUEEN,
@@ -241,15 +373,21 @@ impl Board {
                 _ => {}
             }
         }
+        self.hash ^= zobrist::castling_key(self.castling_rights);
 
         self.side_to_move = enemy;
+        self.hash ^= zobrist::side_key();
+
+        debug_assert_eq!(self.hash, self.compute_hash());
+        debug_assert_eq!(self.eval, EvalState::compute(self));
 
         undo
     }
 
     pub fn unmake_move(&mut self, mv: Move, undo: Undo) {
-        let from_mask = bb(mv.from);
-        let to_mask = bb(mv.to);
+        let (from, to) = (mv.from(), mv.to());
+        let from_mask = bb(from);
+        let to_mask = bb(to);
 
         let color = undo.side_to_move;
 
@@ -257,77 +395,48 @@ impl Board {
         self.side_to_move = color;
         self.castling_rights = undo.castling_rights;
         self.en_passant_square = undo.en_passant_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+        self.eval = undo.eval;
+        self.history.pop();
+        if color == Color::Black {
+            self.fullmove_number -= 1;
+        }
 
-        // Check if this was a castling move
-        let is_castling = mv.from.abs_diff(mv.to) == 2 && 
-                          self.pieces[color.index()][Piece::King.index()] & to_mask != 0;
-
-        if is_castling {
-            // Move king back
-            self.pieces[color.index()][Piece::King.index()] ^= to_mask;
-            self.pieces[color.index()][Piece::King.index()] |= from_mask;
-
-            // Move rook back
-            match (color, mv.to) {
-                (Color::White, 6) => { // Kingside castle
-                    self.pieces[0][Piece::Rook.index()] ^= bb(5); // Remove from f1
-                    self.pieces[0][Piece::Rook.index()] |= bb(7); // Add to h1
-                },
-                (Color::White, 2) => { // Queenside castle
-                    self.pieces[0][Piece::Rook.index()] ^= bb(3); // Remove from d1
-                    self.pieces[0][Piece::Rook.index()] |= bb(0); // Add to a1
-                },
-                (Color::Black, 62) => { // Kingside castle
-                    self.pieces[1][Piece::Rook.index()] ^= bb(61); // Remove from f8
-                    self.pieces[1][Piece::Rook.index()] |= bb(63); // Add to h8
-                },
-                (Color::Black, 58) => { // Queenside castle
-                    self.pieces[1][Piece::Rook.index()] ^= bb(59); // Remove from d8
-                    self.pieces[1][Piece::Rook.index()] |= bb(56); // Add to a8
-                },
-                _ => {}
-            }
+        if mv.is_castle() {
+            // Move king and rook back
+            let (rook_from, rook_to) = castling_rook_squares(to);
+            self.pieces[color.index()][Piece::King.index()] ^= from_mask | to_mask;
+            self.pieces[color.index()][Piece::Rook.index()] ^= bb(rook_from) | bb(rook_to);
         } else {
-            // Handle promotion undo
-            if let Some(promo_piece) = mv.promotion {
-                // Remove the promoted piece from destination
-                self.pieces[color.index()][promo_piece as usize] ^= to_mask;
-                // Add the pawn back to the from square
+            if let Some(promo_piece) = mv.promotion() {
+                // Remove the promoted piece and put the pawn back
+                self.pieces[color.index()][promo_piece.index()] ^= to_mask;
                 self.pieces[color.index()][Piece::Pawn.index()] |= from_mask;
             } else {
                 // Move piece back
                 for p in 0..6 {
                     if self.pieces[color.index()][p] & to_mask != 0 {
-                        self.pieces[color.index()][p] ^= to_mask;
-                        self.pieces[color.index()][p] |= from_mask;
+                        self.pieces[color.index()][p] ^= to_mask | from_mask;
                         break;
                     }
                 }
             }
 
-            // Check if this was an en passant capture
-            // En passant is when: pawn captured, and the to square was the en passant square
-            let was_en_passant = self.pieces[color.index()][Piece::Pawn.index()] & from_mask != 0 
-                                 && undo.captured.is_some() 
-                                 && undo.captured.unwrap().1 == Piece::Pawn 
-                                 && undo.en_passant_square == Some(mv.to);
-
             // Restore captured piece
             if let Some((c, piece)) = undo.captured {
-                if was_en_passant {
-                    // Restore the pawn to the correct square (not the 'to' square)
-                    let captured_pawn_sq = match color {
-                        Color::White => mv.to - 8,
-                        Color::Black => mv.to + 8,
-                    };
-                    self.pieces[c.index()][piece.index()] |= bb(captured_pawn_sq);
+                let captured_sq = if mv.is_en_passant() {
+                    en_passant_victim(color, to)
                 } else {
-                    self.pieces[c.index()][piece.index()] |= to_mask;
-                }
+                    to
+                };
+                self.pieces[c.index()][piece.index()] |= bb(captured_sq);
             }
         }
 
         self.recompute_occupancy();
+
+        debug_assert_eq!(self.hash, self.compute_hash());
     }
 
     pub fn is_square_attacked(&self, sq: u8, by: Color) -> bool {
@@ -385,6 +494,26 @@ impl Board {
         false
     }
 
+    /// All pieces of either color attacking `sq`, with `occupied` as the blockers.
+    pub fn attackers_to(&self, sq: u8, occupied: Bitboard) -> Bitboard {
+        let white = &self.pieces[Color::White.index()];
+        let black = &self.pieces[Color::Black.index()];
+
+        let knights = white[Piece::Knight.index()] | black[Piece::Knight.index()];
+        let kings = white[Piece::King.index()] | black[Piece::King.index()];
+        let queens = white[Piece::Queen.index()] | black[Piece::Queen.index()];
+        let bishops = white[Piece::Bishop.index()] | black[Piece::Bishop.index()] | queens;
+        let rooks = white[Piece::Rook.index()] | black[Piece::Rook.index()] | queens;
+
+        // A white pawn attacks `sq` from the squares a black pawn on `sq` would attack
+        (black_pawn_attacks(sq) & white[Piece::Pawn.index()])
+            | (white_pawn_attacks(sq) & black[Piece::Pawn.index()])
+            | (knight_attacks(sq) & knights)
+            | (bishop_attacks(sq, occupied) & bishops)
+            | (rook_attacks(sq, occupied) & rooks)
+            | (king_attacks(sq) & kings)
+    }
+
     pub fn in_check(&self, color: Color) -> bool {
         let king_bb = self.pieces[color.index()][Piece::King.index()];
         debug_assert!(king_bb != 0);
@@ -394,6 +523,12 @@ impl Board {
     }
 }
 
+/// A quiet move between two named squares, for the test modules below.
+#[cfg(test)]
+fn mv(from: Square, to: Square) -> Move {
+    Move::new(from as u8, to as u8, crate::board::r#move::QUIET)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -423,18 +558,14 @@ mod tests {
 mod make_unmake_tests {
     use super::*;
     use crate::board::bitboard::Square;
-    use crate::board::r#move::Move;
+    use crate::board::r#move::*;
     use crate::board::piece::{Color, Piece};
 
     #[test]
     fn make_and_unmake_quiet_move() {
         let mut board = Board::startpos();
 
-        let mv = Move {
-            from: Square::G1 as u8,
-            to: Square::F3 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::G1 as u8, Square::F3 as u8, QUIET);
 
         let undo = board.make_move(mv);
 
@@ -453,13 +584,9 @@ mod make_unmake_tests {
 
         board.pieces[0][Piece::Knight.index()] = bb(Square::E4 as u8);
         board.pieces[1][Piece::Pawn.index()] = bb(Square::D6 as u8);
-        board.recompute_occupancy();
+        board.refresh();
 
-        let mv = Move {
-            from: Square::E4 as u8,
-            to: Square::D6 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E4 as u8, Square::D6 as u8, CAPTURE);
 
         let undo = board.make_move(mv);
         assert!(board.piece_at(Square::D6 as u8).unwrap().0 == Color::White);
@@ -474,11 +601,7 @@ mod make_unmake_tests {
         let mut board = Board::startpos();
         let original = board.clone();
 
-        let mv = Move {
-            from: Square::B1 as u8,
-            to: Square::C3 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::B1 as u8, Square::C3 as u8, QUIET);
 
         let undo = board.make_move(mv);
         board.unmake_move(mv, undo);
@@ -499,7 +622,7 @@ mod check_tests {
         let mut board = Board::empty();
         board.pieces[Color::White.index()][Piece::King.index()] = bb(Square::E4 as u8);
         board.pieces[Color::Black.index()][Piece::Knight.index()] = bb(Square::F6 as u8);
-        board.recompute_occupancy();
+        board.refresh();
 
         assert!(board.in_check(Color::White));
     }
@@ -510,7 +633,7 @@ mod check_tests {
         board.pieces[0][Piece::King.index()] = bb(Square::E4 as u8);
         board.pieces[1][Piece::Bishop.index()] = bb(Square::A8 as u8);
         board.pieces[0][Piece::Pawn.index()] = bb(Square::C6 as u8);
-        board.recompute_occupancy();
+        board.refresh();
 
         assert!(!board.in_check(Color::White));
     }
@@ -520,7 +643,7 @@ mod check_tests {
         let mut board = Board::empty();
         board.pieces[0][Piece::King.index()] = bb(Square::E1 as u8);
         board.pieces[1][Piece::Rook.index()] = bb(Square::E8 as u8);
-        board.recompute_occupancy();
+        board.refresh();
 
         assert!(board.in_check(Color::White));
     }
@@ -530,7 +653,7 @@ mod check_tests {
         let mut board = Board::empty();
         board.pieces[0][Piece::King.index()] = bb(Square::E4 as u8);
         board.pieces[1][Piece::Pawn.index()] = bb(Square::D5 as u8);
-        board.recompute_occupancy();
+        board.refresh();
 
         assert!(board.in_check(Color::White));
     }
@@ -540,7 +663,7 @@ mod check_tests {
         let mut board = Board::empty();
         board.pieces[0][Piece::King.index()] = bb(Square::E4 as u8);
         board.pieces[1][Piece::King.index()] = bb(Square::E5 as u8);
-        board.recompute_occupancy();
+        board.refresh();
 
         assert!(board.in_check(Color::White));
     }
@@ -551,17 +674,13 @@ mod en_passant_tests {
     use super::*;
     use crate::board::bitboard::Square;
     use crate::board::piece::{Color, Piece};
-    use crate::board::r#move::Move;
+    use crate::board::r#move::*;
 
     #[test]
     fn white_pawn_double_push_sets_en_passant() {
         let mut board = Board::startpos();
         
-        let mv = Move {
-            from: Square::E2 as u8,
-            to: Square::E4 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E2 as u8, Square::E4 as u8, DOUBLE_PUSH);
         
         board.make_move(mv);
         
@@ -572,12 +691,9 @@ mod en_passant_tests {
     fn black_pawn_double_push_sets_en_passant() {
         let mut board = Board::startpos();
         board.side_to_move = Color::Black;
+        board.refresh();
         
-        let mv = Move {
-            from: Square::D7 as u8,
-            to: Square::D5 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::D7 as u8, Square::D5 as u8, DOUBLE_PUSH);
         
         board.make_move(mv);
         
@@ -590,13 +706,9 @@ mod en_passant_tests {
         board.pieces[Color::White.index()][Piece::Pawn.index()] = bb(Square::E5 as u8);
         board.pieces[Color::Black.index()][Piece::Pawn.index()] = bb(Square::D5 as u8);
         board.en_passant_square = Some(Square::D6 as u8);
-        board.recompute_occupancy();
+        board.refresh();
         
-        let mv = Move {
-            from: Square::E5 as u8,
-            to: Square::D6 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E5 as u8, Square::D6 as u8, EN_PASSANT);
         
         board.make_move(mv);
         
@@ -615,13 +727,9 @@ mod en_passant_tests {
         board.pieces[Color::White.index()][Piece::Pawn.index()] = bb(Square::E4 as u8);
         board.en_passant_square = Some(Square::E3 as u8);
         board.side_to_move = Color::Black;
-        board.recompute_occupancy();
+        board.refresh();
         
-        let mv = Move {
-            from: Square::F4 as u8,
-            to: Square::E3 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::F4 as u8, Square::E3 as u8, EN_PASSANT);
         
         board.make_move(mv);
         
@@ -638,20 +746,12 @@ mod en_passant_tests {
         let mut board = Board::startpos();
         
         // White pawn double push
-        let mv1 = Move {
-            from: Square::E2 as u8,
-            to: Square::E4 as u8,
-            promotion: None,
-        };
+        let mv1 = Move::new(Square::E2 as u8, Square::E4 as u8, DOUBLE_PUSH);
         board.make_move(mv1);
         assert_eq!(board.en_passant_square, Some(Square::E3 as u8));
         
         // Black makes a different move
-        let mv2 = Move {
-            from: Square::B8 as u8,
-            to: Square::C6 as u8,
-            promotion: None,
-        };
+        let mv2 = Move::new(Square::B8 as u8, Square::C6 as u8, QUIET);
         board.make_move(mv2);
         
         // En passant should be cleared
@@ -664,15 +764,11 @@ mod en_passant_tests {
         board.pieces[Color::White.index()][Piece::Pawn.index()] = bb(Square::E5 as u8);
         board.pieces[Color::Black.index()][Piece::Pawn.index()] = bb(Square::D5 as u8);
         board.en_passant_square = Some(Square::D6 as u8);
-        board.recompute_occupancy();
+        board.refresh();
         
         let original = board.clone();
         
-        let mv = Move {
-            from: Square::E5 as u8,
-            to: Square::D6 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E5 as u8, Square::D6 as u8, EN_PASSANT);
         
         let undo = board.make_move(mv);
         board.unmake_move(mv, undo);
@@ -691,7 +787,7 @@ mod castling_tests {
     use super::*;
     use crate::board::bitboard::Square;
     use crate::board::piece::{Color, Piece};
-    use crate::board::r#move::Move;
+    use crate::board::r#move::*;
 
     #[test]
     fn white_kingside_castle_moves_rook() {
@@ -699,13 +795,9 @@ mod castling_tests {
         board.pieces[Color::White.index()][Piece::King.index()] = bb(Square::E1 as u8);
         board.pieces[Color::White.index()][Piece::Rook.index()] = bb(Square::H1 as u8);
         board.castling_rights = CASTLE_WHITE_KING;
-        board.recompute_occupancy();
+        board.refresh();
         
-        let mv = Move {
-            from: Square::E1 as u8,
-            to: Square::G1 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E1 as u8, Square::G1 as u8, KING_CASTLE);
         
         board.make_move(mv);
         
@@ -723,13 +815,9 @@ mod castling_tests {
         board.pieces[Color::White.index()][Piece::King.index()] = bb(Square::E1 as u8);
         board.pieces[Color::White.index()][Piece::Rook.index()] = bb(Square::A1 as u8);
         board.castling_rights = CASTLE_WHITE_QUEEN;
-        board.recompute_occupancy();
+        board.refresh();
         
-        let mv = Move {
-            from: Square::E1 as u8,
-            to: Square::C1 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E1 as u8, Square::C1 as u8, QUEEN_CASTLE);
         
         board.make_move(mv);
         
@@ -748,13 +836,9 @@ mod castling_tests {
         board.pieces[Color::Black.index()][Piece::Rook.index()] = bb(Square::H8 as u8);
         board.castling_rights = CASTLE_BLACK_KING;
         board.side_to_move = Color::Black;
-        board.recompute_occupancy();
+        board.refresh();
         
-        let mv = Move {
-            from: Square::E8 as u8,
-            to: Square::G8 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E8 as u8, Square::G8 as u8, KING_CASTLE);
         
         board.make_move(mv);
         
@@ -773,13 +857,9 @@ mod castling_tests {
         board.pieces[Color::Black.index()][Piece::Rook.index()] = bb(Square::A8 as u8);
         board.castling_rights = CASTLE_BLACK_QUEEN;
         board.side_to_move = Color::Black;
-        board.recompute_occupancy();
+        board.refresh();
         
-        let mv = Move {
-            from: Square::E8 as u8,
-            to: Square::C8 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E8 as u8, Square::C8 as u8, QUEEN_CASTLE);
         
         board.make_move(mv);
         
@@ -795,11 +875,7 @@ mod castling_tests {
     fn castling_rights_removed_after_king_move() {
         let mut board = Board::startpos();
         
-        let mv = Move {
-            from: Square::E1 as u8,
-            to: Square::E2 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E1 as u8, Square::E2 as u8, QUIET);
         
         board.make_move(mv);
         
@@ -814,11 +890,7 @@ mod castling_tests {
         let mut board = Board::startpos();
         
         // Move h1 rook
-        let mv = Move {
-            from: Square::H1 as u8,
-            to: Square::H2 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::H1 as u8, Square::H2 as u8, QUIET);
         
         board.make_move(mv);
         
@@ -836,14 +908,10 @@ mod castling_tests {
         board.pieces[Color::Black.index()][Piece::King.index()] = bb(Square::E8 as u8);
         board.castling_rights = CASTLE_WHITE_KING | CASTLE_WHITE_QUEEN;
         board.side_to_move = Color::Black;
-        board.recompute_occupancy();
+        board.refresh();
         
         // Black bishop captures a1 rook
-        let mv = Move {
-            from: Square::C3 as u8,
-            to: Square::A1 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::C3 as u8, Square::A1 as u8, CAPTURE);
         
         board.make_move(mv);
         
@@ -859,15 +927,11 @@ mod castling_tests {
         board.pieces[Color::White.index()][Piece::King.index()] = bb(Square::E1 as u8);
         board.pieces[Color::White.index()][Piece::Rook.index()] = bb(Square::H1 as u8);
         board.castling_rights = CASTLE_WHITE_KING;
-        board.recompute_occupancy();
+        board.refresh();
         
         let original = board.clone();
         
-        let mv = Move {
-            from: Square::E1 as u8,
-            to: Square::G1 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E1 as u8, Square::G1 as u8, KING_CASTLE);
         
         let undo = board.make_move(mv);
         board.unmake_move(mv, undo);
@@ -883,17 +947,203 @@ mod castling_tests {
     #[test]
     fn castling_rights_restored_on_unmake() {
         let mut board = Board::startpos();
+        // Clear e2 so the king move below is legal
+        board.pieces[Color::White.index()][Piece::Pawn.index()] ^= bb(Square::E2 as u8);
+        board.refresh();
         let original_rights = board.castling_rights;
         
-        let mv = Move {
-            from: Square::E1 as u8,
-            to: Square::E2 as u8,
-            promotion: None,
-        };
+        let mv = Move::new(Square::E1 as u8, Square::E2 as u8, QUIET);
         
         let undo = board.make_move(mv);
         board.unmake_move(mv, undo);
         
         assert_eq!(board.castling_rights, original_rights);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod hash_tests {
+    use super::*;
+    use crate::board::bitboard::Square;
+    use crate::board::r#move::*;
+
+    #[test]
+    fn startpos_hash_matches_fen() {
+        let board = Board::startpos();
+        let fen = Board::from_fen(crate::board::fen::STARTPOS_FEN).unwrap();
+        assert_eq!(board.hash, board.compute_hash());
+        assert_eq!(board.hash, fen.hash);
+    }
+
+    #[test]
+    fn transpositions_share_a_hash() {
+        let mut a = Board::startpos();
+        for m in [mv(Square::G1, Square::F3), mv(Square::G8, Square::F6), mv(Square::B1, Square::C3)] {
+            a.make_move(m);
+        }
+
+        let mut b = Board::startpos();
+        for m in [mv(Square::B1, Square::C3), mv(Square::G8, Square::F6), mv(Square::G1, Square::F3)] {
+            b.make_move(m);
+        }
+
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.hash, a.compute_hash());
+    }
+
+    #[test]
+    fn en_passant_and_side_change_hash() {
+        // Black can take on e3 from d4
+        let before = "rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let mut board = Board::from_fen(before).unwrap();
+        board.make_move(Move::new(Square::E2 as u8, Square::E4 as u8, DOUBLE_PUSH));
+
+        let with_ep = Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let without_ep = Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        let white_to_move = Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1").unwrap();
+
+        assert_eq!(board.hash, with_ep.hash);
+        assert_ne!(with_ep.hash, without_ep.hash);
+        assert_ne!(without_ep.hash, white_to_move.hash);
+    }
+
+    #[test]
+    fn uncapturable_en_passant_is_not_hashed() {
+        let mut board = Board::startpos();
+        board.make_move(Move::new(Square::E2 as u8, Square::E4 as u8, DOUBLE_PUSH));
+
+        let without_ep = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_eq!(board.en_passant_square, Some(Square::E3 as u8));
+        assert_eq!(board.hash, without_ep.hash);
+    }
+
+    #[test]
+    fn hash_restored_on_unmake() {
+        let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let original = board.hash;
+
+        for m in [
+            Move::new(Square::E1 as u8, Square::G1 as u8, KING_CASTLE),
+            Move::new(Square::E1 as u8, Square::C1 as u8, QUEEN_CASTLE),
+            Move::new(Square::E5 as u8, Square::F7 as u8, CAPTURE),
+            Move::new(Square::D5 as u8, Square::E6 as u8, CAPTURE),
+            mv(Square::H1, Square::F1),
+        ] {
+            let undo = board.make_move(m);
+            assert_ne!(board.hash, original);
+            board.unmake_move(m, undo);
+            assert_eq!(board.hash, original);
+        }
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::*;
+    use crate::board::bitboard::Square;
+    use crate::board::r#move::*;
+
+    #[test]
+    fn quiet_moves_advance_clocks() {
+        let mut board = Board::startpos();
+
+        board.make_move(mv(Square::G1, Square::F3));
+        assert_eq!(board.halfmove_clock, 1);
+        assert_eq!(board.fullmove_number, 1);
+
+        board.make_move(mv(Square::G8, Square::F6));
+        assert_eq!(board.halfmove_clock, 2);
+        assert_eq!(board.fullmove_number, 2);
+    }
+
+    #[test]
+    fn pawn_moves_and_captures_reset_halfmove_clock() {
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/2N5/4P3/4K3 w - - 12 30").unwrap();
+
+        board.make_move(Move::new(Square::E2 as u8, Square::E4 as u8, DOUBLE_PUSH));
+        assert_eq!(board.halfmove_clock, 0);
+
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/2N5/4P3/4K3 w - - 12 30").unwrap();
+        board.make_move(Move::new(Square::C3 as u8, Square::D5 as u8, CAPTURE));
+        assert_eq!(board.halfmove_clock, 0);
+    }
+
+    #[test]
+    fn unmake_restores_clocks() {
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/2N5/4P3/4K3 b - - 12 30").unwrap();
+
+        let m = mv(Square::D5, Square::D4);
+        let undo = board.make_move(m);
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 31);
+
+        board.unmake_move(m, undo);
+        assert_eq!(board.halfmove_clock, 12);
+        assert_eq!(board.fullmove_number, 30);
+    }
+}
+
+#[cfg(test)]
+mod repetition_tests {
+    use super::*;
+    use crate::board::bitboard::Square;
+    use crate::board::r#move::*;
+
+    fn shuffle_knights(board: &mut Board) {
+        for m in [
+            mv(Square::G1, Square::F3),
+            mv(Square::G8, Square::F6),
+            mv(Square::F3, Square::G1),
+            mv(Square::F6, Square::G8),
+        ] {
+            board.make_move(m);
+        }
+    }
+
+    #[test]
+    fn detects_repetitions() {
+        let mut board = Board::startpos();
+        assert!(!board.is_repetition(1));
+
+        shuffle_knights(&mut board);
+        assert!(board.is_repetition(1));
+        assert!(!board.is_draw());
+
+        shuffle_knights(&mut board);
+        assert!(board.is_repetition(2));
+        assert!(board.is_draw());
+
+        // The start position is at index 0, 4 and now 8
+        assert!(board.is_repetition_since(4));
+        assert!(!board.is_repetition_since(5));
+    }
+
+    #[test]
+    fn irreversible_move_breaks_repetition() {
+        let mut board = Board::startpos();
+        shuffle_knights(&mut board);
+        board.make_move(Move::new(Square::E2 as u8, Square::E4 as u8, DOUBLE_PUSH));
+        board.make_move(Move::new(Square::E7 as u8, Square::E5 as u8, DOUBLE_PUSH));
+        shuffle_knights(&mut board);
+
+        assert!(board.is_repetition(1));
+        assert!(!board.is_repetition(2));
+    }
+
+    #[test]
+    fn unmake_pops_history() {
+        let mut board = Board::startpos();
+        let m = mv(Square::G1, Square::F3);
+
+        let undo = board.make_move(m);
+        assert_eq!(board.history.len(), 1);
+
+        board.unmake_move(m, undo);
+        assert!(board.history.is_empty());
+    }
+
+    #[test]
+    fn fifty_move_rule_is_draw() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 100 80").unwrap();
+        assert!(board.is_draw());
+    }
+}
@@ -0,0 +1,390 @@
+use std::fmt;
+
+use crate::board::bitboard::*;
+use crate::board::board::Board;
+use crate::board::piece::{Color, Piece};
+
+pub const STARTPOS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    TooManyFields,
+    InvalidPiece(char),
+    WrongRankCount(usize),
+    WrongRankLength { rank: u8, squares: usize },
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    WrongKingCount { color: Color, count: u32 },
+    PawnOnBackRank,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "missing {} field", field),
+            FenError::TooManyFields => write!(f, "too many fields"),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece character '{}'", c),
+            FenError::WrongRankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::WrongRankLength { rank, squares } => {
+                write!(f, "rank {} describes {} squares, expected 8", rank, squares)
+            }
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::InvalidCastling(s) => write!(f, "invalid castling rights '{}'", s),
+            FenError::InvalidEnPassant(s) => write!(f, "invalid en passant square '{}'", s),
+            FenError::InvalidHalfmoveClock(s) => write!(f, "invalid halfmove clock '{}'", s),
+            FenError::InvalidFullmoveNumber(s) => write!(f, "invalid fullmove number '{}'", s),
+            FenError::WrongKingCount { color, count } => {
+                write!(f, "{:?} has {} kings, expected 1", color, count)
+            }
+            FenError::PawnOnBackRank => write!(f, "pawn on the first or eighth rank"),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+fn piece_from_char(c: char) -> Option<(Color, Piece)> {
+    let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+    let piece = match c.to_ascii_lowercase() {
+        'p' => Piece::Pawn,
+        'n' => Piece::Knight,
+        'b' => Piece::Bishop,
+        'r' => Piece::Rook,
+        'q' => Piece::Queen,
+        'k' => Piece::King,
+        _ => return None,
+    };
+    Some((color, piece))
+}
+
+fn piece_to_char(color: Color, piece: Piece) -> char {
+    let c = match piece {
+        Piece::Pawn => 'p',
+        Piece::Knight => 'n',
+        Piece::Bishop => 'b',
+        Piece::Rook => 'r',
+        Piece::Queen => 'q',
+        Piece::King => 'k',
+    };
+    match color {
+        Color::White => c.to_ascii_uppercase(),
+        Color::Black => c,
+    }
+}
+
+/// Parse a square name such as "e3" into a square index.
+pub fn parse_square(s: &str) -> Option<u8> {
+    let bytes = s.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let file = bytes[0].wrapping_sub(b'a');
+    let rank = bytes[1].wrapping_sub(b'1');
+    if file < 8 && rank < 8 {
+        Some(rank * 8 + file)
+    } else {
+        None
+    }
+}
+
+pub fn square_name(sq: u8) -> String {
+    format!("{}{}", (b'a' + file_of(sq)) as char, (b'1' + rank_of(sq)) as char)
+}
+
+fn parse_placement(board: &mut Board, placement: &str) -> Result<(), FenError> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::WrongRankCount(ranks.len()));
+    }
+
+    // FEN lists rank 8 first
+    for (i, rank_str) in ranks.iter().enumerate() {
+        let rank = 7 - i as u8;
+        let mut file = 0usize;
+
+        for c in rank_str.chars() {
+            if let Some(skip) = c.to_digit(10) {
+                if !(1..=8).contains(&skip) {
+                    return Err(FenError::InvalidPiece(c));
+                }
+                file += skip as usize;
+            } else {
+                let (color, piece) = piece_from_char(c).ok_or(FenError::InvalidPiece(c))?;
+                if file < 8 {
+                    board.pieces[color.index()][piece.index()] |= bb(rank * 8 + file as u8);
+                }
+                file += 1;
+            }
+        }
+
+        if file != 8 {
+            return Err(FenError::WrongRankLength { rank: rank + 1, squares: file });
+        }
+    }
+
+    for color in [Color::White, Color::Black] {
+        let count = board.pieces[color.index()][Piece::King.index()].count_ones();
+        if count != 1 {
+            return Err(FenError::WrongKingCount { color, count });
+        }
+    }
+
+    let pawns = board.pieces[0][Piece::Pawn.index()] | board.pieces[1][Piece::Pawn.index()];
+    if pawns & (RANK_1 | RANK_8) != 0 {
+        return Err(FenError::PawnOnBackRank);
+    }
+
+    Ok(())
+}
+
+fn parse_castling(s: &str) -> Result<u8, FenError> {
+    if s == "-" {
+        return Ok(0);
+    }
+
+    let mut rights = 0;
+    for c in s.chars() {
+        let flag = match c {
+            'K' => CASTLE_WHITE_KING,
+            'Q' => CASTLE_WHITE_QUEEN,
+            'k' => CASTLE_BLACK_KING,
+            'q' => CASTLE_BLACK_QUEEN,
+            _ => return Err(FenError::InvalidCastling(s.to_string())),
+        };
+        if rights & flag != 0 {
+            return Err(FenError::InvalidCastling(s.to_string()));
+        }
+        rights |= flag;
+    }
+    Ok(rights)
+}
+
+/// Every castling right needs its king and rook on their starting squares.
+fn castling_pieces_present(board: &Board, rights: u8) -> bool {
+    [
+        (CASTLE_WHITE_KING, Color::White, Square::E1, Square::H1),
+        (CASTLE_WHITE_QUEEN, Color::White, Square::E1, Square::A1),
+        (CASTLE_BLACK_KING, Color::Black, Square::E8, Square::H8),
+        (CASTLE_BLACK_QUEEN, Color::Black, Square::E8, Square::A8),
+    ]
+    .iter()
+    .all(|&(flag, color, king, rook)| {
+        let pieces = &board.pieces[color.index()];
+        rights & flag == 0
+            || (pieces[Piece::King.index()] & bb(king as u8) != 0 && pieces[Piece::Rook.index()] & bb(rook as u8) != 0)
+    })
+}
+
+/// Whether an enemy pawn can just have double pushed past `square`: it is on
+/// the right rank, the pawn stands in front of it and the square it started
+/// from is empty, as is `square` itself.
+fn double_push_behind(board: &Board, square: u8) -> bool {
+    let mover = board.side_to_move.opposite();
+    let (rank, victim, origin) = match mover {
+        Color::Black => (5, square.wrapping_sub(8), square.wrapping_add(8)),
+        Color::White => (2, square.wrapping_add(8), square.wrapping_sub(8)),
+    };
+    rank_of(square) == rank
+        && board.pieces[mover.index()][Piece::Pawn.index()] & bb(victim) != 0
+        && board.piece_at(square).is_none()
+        && board.piece_at(origin).is_none()
+}
+
+impl Board {
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+
+        let placement = fields.first().ok_or(FenError::MissingField("piece placement"))?;
+        let side = fields.get(1).ok_or(FenError::MissingField("side to move"))?;
+        let castling = fields.get(2).ok_or(FenError::MissingField("castling rights"))?;
+        let en_passant = fields.get(3).ok_or(FenError::MissingField("en passant"))?;
+        if fields.len() > 6 {
+            return Err(FenError::TooManyFields);
+        }
+
+        let mut board = Board::empty();
+        parse_placement(&mut board, placement)?;
+
+        board.side_to_move = match *side {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+
+        board.castling_rights = parse_castling(castling)?;
+        if !castling_pieces_present(&board, board.castling_rights) {
+            return Err(FenError::InvalidCastling(castling.to_string()));
+        }
+
+        board.en_passant_square = if *en_passant == "-" {
+            None
+        } else {
+            // The en passant square must sit behind a pawn that just double pushed
+            match parse_square(en_passant) {
+                Some(sq) if double_push_behind(&board, sq) => Some(sq),
+                _ => return Err(FenError::InvalidEnPassant(en_passant.to_string())),
+            }
+        };
+
+        // Move clocks are optional, many EPD-style strings omit them
+        if let Some(halfmove) = fields.get(4) {
+            board.halfmove_clock = halfmove
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(halfmove.to_string()))?;
+        }
+        if let Some(fullmove) = fields.get(5) {
+            board.fullmove_number = match fullmove.parse() {
+                Ok(n) if n >= 1 => n,
+                _ => return Err(FenError::InvalidFullmoveNumber(fullmove.to_string())),
+            };
+        }
+
+        board.refresh();
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at(rank * 8 + file) {
+                    Some((color, piece)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_to_char(color, piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.side_to_move {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+
+        fen.push(' ');
+        if self.castling_rights == 0 {
+            fen.push('-');
+        } else {
+            for (flag, c) in [
+                (CASTLE_WHITE_KING, 'K'),
+                (CASTLE_WHITE_QUEEN, 'Q'),
+                (CASTLE_BLACK_KING, 'k'),
+                (CASTLE_BLACK_QUEEN, 'q'),
+            ] {
+                if self.castling_rights & flag != 0 {
+                    fen.push(c);
+                }
+            }
+        }
+
+        fen.push(' ');
+        match self.en_passant_square {
+            Some(sq) => fen.push_str(&square_name(sq)),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::bitboard::Square;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    #[test]
+    fn startpos_matches_board() {
+        let from_fen = Board::from_fen(STARTPOS_FEN).unwrap();
+        let start = Board::startpos();
+
+        assert_eq!(from_fen.pieces, start.pieces);
+        assert_eq!(from_fen.occupied, start.occupied);
+        assert_eq!(from_fen.side_to_move, start.side_to_move);
+        assert_eq!(from_fen.castling_rights, start.castling_rights);
+        assert_eq!(from_fen.en_passant_square, start.en_passant_square);
+        assert_eq!(start.to_fen(), STARTPOS_FEN);
+    }
+
+    #[test]
+    fn round_trip() {
+        for fen in [
+            STARTPOS_FEN,
+            KIWIPETE,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 3 17",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn parses_fields() {
+        let board = Board::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kq f6 4 12").unwrap();
+
+        assert_eq!(board.side_to_move, Color::White);
+        assert_eq!(board.castling_rights, CASTLE_WHITE_KING | CASTLE_BLACK_QUEEN);
+        assert_eq!(board.en_passant_square, Some(Square::F6 as u8));
+        assert_eq!(board.halfmove_clock, 4);
+        assert_eq!(board.fullmove_number, 12);
+        assert_eq!(board.piece_at(Square::E5 as u8), Some((Color::White, Piece::Pawn)));
+    }
+
+    #[test]
+    fn clocks_are_optional() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 1);
+        assert_eq!(board.side_to_move, Color::Black);
+    }
+
+    #[test]
+    fn rejects_malformed_fields() {
+        let cases = [
+            ("", FenError::MissingField("piece placement")),
+            ("4k3/8/8/8/8/8/8/4K3", FenError::MissingField("side to move")),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 extra", FenError::TooManyFields),
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::WrongRankCount(7)),
+            ("4k3/8/8/8/8/8/8/4K4 w - - 0 1", FenError::WrongRankLength { rank: 1, squares: 9 }),
+            ("4k3/8/8/8/8/8/8/4X3 w - - 0 1", FenError::InvalidPiece('X')),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::InvalidSideToMove("x".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w KX - 0 1", FenError::InvalidCastling("KX".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", FenError::InvalidCastling("K".to_string())),
+            ("r3k3/8/8/8/8/8/8/4K2R w KQkq - 0 1", FenError::InvalidCastling("KQkq".to_string())),
+            ("3k3r/8/8/8/8/8/8/R3K2R w k - 0 1", FenError::InvalidCastling("k".to_string())),
+            ("4k3/8/8/8/8/8/8/R4K1R w K - 0 1", FenError::InvalidCastling("K".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w - e4 0 1", FenError::InvalidEnPassant("e4".to_string())),
+            ("4k3/8/8/3P4/8/8/8/4K3 w - e6 0 1", FenError::InvalidEnPassant("e6".to_string())),
+            ("4k3/8/4n3/3Pp3/8/8/8/4K3 w - e6 0 1", FenError::InvalidEnPassant("e6".to_string())),
+            ("4k3/4p3/8/3Pp3/8/8/8/4K3 w - e6 0 1", FenError::InvalidEnPassant("e6".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenError::InvalidHalfmoveClock("x".to_string())),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 0", FenError::InvalidFullmoveNumber("0".to_string())),
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::WrongKingCount { color: Color::Black, count: 0 }),
+            ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", FenError::PawnOnBackRank),
+        ];
+
+        for (fen, expected) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(expected), "{}", fen);
+        }
+    }
+}
@@ -1,5 +1,8 @@
 pub mod bitboard;
+#[allow(clippy::module_inception)]
 pub mod board;
+pub mod fen;
 pub mod piece;
 pub mod r#move;
-pub mod undo;
\ No newline at end of file
+pub mod undo;
+pub mod zobrist;
\ No newline at end of file
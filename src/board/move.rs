@@ -1,62 +1,380 @@
-#[derive(Copy, Clone, Debug)]
-pub struct Move {
-    pub from: u8,
-    pub to: u8,
-    pub promotion: Option<u8>, // later: piece index
-}
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+
+use crate::board::fen::parse_square;
+use crate::board::piece::{Piece, index_to_piece};
 
-pub type MoveList = Vec<Move>;
+// Move flags, stored in the top four bits. Bit 2 marks captures and bit 3
+// promotions; a promotion's low two bits hold the piece (knight..queen).
+pub const QUIET: u16 = 0;
+pub const DOUBLE_PUSH: u16 = 1;
+pub const KING_CASTLE: u16 = 2;
+pub const QUEEN_CASTLE: u16 = 3;
+pub const CAPTURE: u16 = 4;
+pub const EN_PASSANT: u16 = 5;
+pub const PROMOTION: u16 = 8;
+pub const PROMOTION_CAPTURE: u16 = 12;
+
+/// A move packed into 16 bits: from (6), to (6), flags (4).
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Move(u16);
 
 impl Move {
+    /// Placeholder for "no move"; a1a1 can never be played.
+    pub const NULL: Move = Move(0);
+
+    #[inline(always)]
+    pub const fn new(from: u8, to: u8, flags: u16) -> Move {
+        Move(from as u16 | (to as u16) << 6 | flags << 12)
+    }
+
+    #[inline(always)]
+    pub fn new_promotion(from: u8, to: u8, piece: Piece, capture: bool) -> Move {
+        debug_assert!(matches!(piece, Piece::Knight | Piece::Bishop | Piece::Rook | Piece::Queen));
+        let base = if capture { PROMOTION_CAPTURE } else { PROMOTION };
+        Move::new(from, to, base | (piece.index() as u16 - 1))
+    }
+
+    #[inline(always)]
+    pub const fn from_raw(raw: u16) -> Move {
+        Move(raw)
+    }
+
+    #[inline(always)]
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub const fn from(self) -> u8 {
+        (self.0 & 0x3F) as u8
+    }
+
+    #[inline(always)]
+    pub const fn to(self) -> u8 {
+        ((self.0 >> 6) & 0x3F) as u8
+    }
+
+    #[inline(always)]
+    pub const fn flags(self) -> u16 {
+        self.0 >> 12
+    }
+
+    #[inline(always)]
+    pub const fn is_null(self) -> bool {
+        self.0 == 0
+    }
+
+    /// True for every capture, including en passant and capturing promotions.
+    #[inline(always)]
+    pub const fn is_capture(self) -> bool {
+        self.flags() & CAPTURE != 0
+    }
+
+    #[inline(always)]
+    pub const fn is_promotion(self) -> bool {
+        self.flags() & PROMOTION != 0
+    }
+
+    #[inline(always)]
+    pub const fn is_en_passant(self) -> bool {
+        self.flags() == EN_PASSANT
+    }
+
+    #[inline(always)]
+    pub const fn is_double_push(self) -> bool {
+        self.flags() == DOUBLE_PUSH
+    }
+
+    #[inline(always)]
+    pub const fn is_castle(self) -> bool {
+        matches!(self.flags(), KING_CASTLE | QUEEN_CASTLE)
+    }
+
+    #[inline(always)]
+    pub fn promotion(self) -> Option<Piece> {
+        if self.is_promotion() {
+            Some(index_to_piece((self.flags() & 3) as usize + 1))
+        } else {
+            None
+        }
+    }
+
     pub fn to_uci(&self) -> String {
+        if self.is_null() {
+            return "0000".to_string();
+        }
+
         let mut result = format!(
             "{}{}{}{}",
-            (b'a' + (self.from % 8) as u8) as char,
-            (b'1' + (self.from / 8) as u8) as char,
-            (b'a' + (self.to % 8) as u8) as char,
-            (b'1' + (self.to / 8) as u8) as char,
+            (b'a' + (self.from() % 8)) as char,
+            (b'1' + (self.from() / 8)) as char,
+            (b'a' + (self.to() % 8)) as char,
+            (b'1' + (self.to() / 8)) as char,
         );
-        
-        if let Some(promo) = self.promotion {
+
+        if let Some(promo) = self.promotion() {
             let promo_char = match promo {
-                1 => 'n',
-                2 => 'b',
-                3 => 'r',
-                4 => 'q',
-                _ => ' ',
+                Piece::Knight => 'n',
+                Piece::Bishop => 'b',
+                Piece::Rook => 'r',
+                _ => 'q',
             };
             result.push(promo_char);
         }
-        
+
         result
     }
 
+    /// Parse coordinate notation. The string alone cannot tell captures,
+    /// castling or en passant apart, so only the promotion flag is set; match
+    /// the result against generated moves to recover the rest.
     pub fn from_uci(uci_str: &str) -> Option<Move> {
-        if uci_str.len() < 4 {
+        if !uci_str.is_ascii() || !(4..=5).contains(&uci_str.len()) {
             return None;
         }
-        
-        let bytes = uci_str.as_bytes();
-        let from_file = (bytes[0] - b'a') as u8;
-        let from_rank = (bytes[1] - b'1') as u8;
-        let to_file = (bytes[2] - b'a') as u8;
-        let to_rank = (bytes[3] - b'1') as u8;
-        
-        let from = from_rank * 8 + from_file;
-        let to = to_rank * 8 + to_file;
-        
-        let promotion = if uci_str.len() > 4 {
-            match bytes[4] {
-                b'n' => Some(1), // Knight
-                b'b' => Some(2), // Bishop
-                b'r' => Some(3), // Rook
-                b'q' => Some(4), // Queen
-                _ => None,
+
+        let from = parse_square(&uci_str[0..2])?;
+        let to = parse_square(&uci_str[2..4])?;
+
+        match uci_str.as_bytes().get(4) {
+            None => Some(Move::new(from, to, QUIET)),
+            Some(b'n') => Some(Move::new_promotion(from, to, Piece::Knight, false)),
+            Some(b'b') => Some(Move::new_promotion(from, to, Piece::Bishop, false)),
+            Some(b'r') => Some(Move::new_promotion(from, to, Piece::Rook, false)),
+            Some(b'q') => Some(Move::new_promotion(from, to, Piece::Queen, false)),
+            Some(_) => None,
+        }
+    }
+}
+
+impl fmt::Debug for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_uci())?;
+        if self.flags() != QUIET {
+            write!(f, " (flags {:#x})", self.flags())?;
+        }
+        Ok(())
+    }
+}
+
+/// No legal chess position has more than 218 moves.
+pub const MAX_MOVES: usize = 256;
+
+/// Fixed-capacity move list that lives on the stack, so generating moves
+/// never allocates. Derefs to a slice for iteration, indexing and `len`.
+pub struct MoveList {
+    moves: [MaybeUninit<Move>; MAX_MOVES], // only the first `len` are initialized
+    len: usize,
+}
+
+impl MoveList {
+    #[inline]
+    pub fn new() -> Self {
+        MoveList {
+            moves: [MaybeUninit::uninit(); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, mv: Move) {
+        debug_assert!(self.len < MAX_MOVES);
+        self.moves[self.len] = MaybeUninit::new(mv);
+        self.len += 1;
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Sort by descending score, keeping generation order among equal scores.
+    /// Each move is scored once.
+    pub fn sort_by_score<F: FnMut(&Move) -> i32>(&mut self, mut score: F) {
+        let mut scores = [0i32; MAX_MOVES];
+        let moves: &mut [Move] = self;
+        for (slot, mv) in scores.iter_mut().zip(moves.iter()) {
+            *slot = score(mv);
+        }
+
+        // Insertion sort: lists are short and often nearly ordered
+        for i in 1..moves.len() {
+            let (mv, s) = (moves[i], scores[i]);
+            let mut j = i;
+            while j > 0 && scores[j - 1] < s {
+                moves[j] = moves[j - 1];
+                scores[j] = scores[j - 1];
+                j -= 1;
             }
-        } else {
-            None
-        };
-        
-        Some(Move { from, to, promotion })
+            moves[j] = mv;
+            scores[j] = s;
+        }
+    }
+}
+
+impl Clone for MoveList {
+    fn clone(&self) -> Self {
+        let mut list = MoveList::new();
+        for &mv in self.iter() {
+            list.push(mv);
+        }
+        list
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    #[inline(always)]
+    fn deref(&self) -> &[Move] {
+        // SAFETY: the first `len` entries were written by `push`
+        unsafe { std::slice::from_raw_parts(self.moves.as_ptr() as *const Move, self.len) }
+    }
+}
+
+impl DerefMut for MoveList {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [Move] {
+        // SAFETY: the first `len` entries were written by `push`
+        unsafe { std::slice::from_raw_parts_mut(self.moves.as_mut_ptr() as *mut Move, self.len) }
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = MoveListIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MoveListIntoIter { list: self, index: 0 }
+    }
+}
+
+pub struct MoveListIntoIter {
+    list: MoveList,
+    index: usize,
+}
+
+impl Iterator for MoveListIntoIter {
+    type Item = Move;
+
+    #[inline]
+    fn next(&mut self) -> Option<Move> {
+        let mv = self.list.get(self.index).copied();
+        self.index += 1;
+        mv
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.list.len().saturating_sub(self.index);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for MoveListIntoIter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(from: u8, to: u8) -> Move {
+        Move::new(from, to, QUIET)
+    }
+
+    #[test]
+    fn push_and_iterate() {
+        let mut list = MoveList::new();
+        assert!(list.is_empty());
+
+        list.push(mv(12, 28));
+        list.push(mv(6, 21));
+
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].to(), 21);
+        assert_eq!(list.iter().map(|m| m.from()).collect::<Vec<_>>(), vec![12, 6]);
+        assert_eq!(list.into_iter().count(), 2);
+    }
+
+    #[test]
+    fn sort_by_score_is_descending_and_stable() {
+        let mut list = MoveList::new();
+        for (from, to) in [(0, 1), (0, 2), (0, 3), (0, 4), (0, 5)] {
+            list.push(mv(from, to));
+        }
+
+        // Scores: 1 -> 10, 2 -> 0, 3 -> 30, 4 -> 10, 5 -> 0
+        list.sort_by_score(|m| [0, 10, 0, 30, 10, 0][m.to() as usize]);
+
+        let order: Vec<u8> = list.iter().map(|m| m.to()).collect();
+        assert_eq!(order, vec![3, 1, 4, 2, 5]);
+    }
+
+    #[test]
+    fn holds_max_moves() {
+        let mut list = MoveList::new();
+        for i in 0..MAX_MOVES {
+            list.push(mv(i as u8 % 64, 0));
+        }
+        assert_eq!(list.len(), MAX_MOVES);
+    }
+
+    #[test]
+    fn packs_squares_and_flags() {
+        let m = Move::new(12, 28, DOUBLE_PUSH);
+        assert_eq!(m.from(), 12);
+        assert_eq!(m.to(), 28);
+        assert!(m.is_double_push());
+        assert!(!m.is_capture());
+        assert_eq!(std::mem::size_of::<Move>(), 2);
+
+        let ep = Move::new(36, 43, EN_PASSANT);
+        assert!(ep.is_capture() && ep.is_en_passant());
+
+        let castle = Move::new(4, 6, KING_CASTLE);
+        assert!(castle.is_castle() && !castle.is_capture());
+    }
+
+    #[test]
+    fn promotion_pieces() {
+        for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+            let quiet = Move::new_promotion(52, 60, piece, false);
+            let capture = Move::new_promotion(52, 61, piece, true);
+            assert_eq!(quiet.promotion(), Some(piece));
+            assert_eq!(capture.promotion(), Some(piece));
+            assert!(!quiet.is_capture() && capture.is_capture());
+        }
+        assert_eq!(Move::new(12, 20, QUIET).promotion(), None);
+    }
+
+    #[test]
+    fn uci_round_trip() {
+        for s in ["e2e4", "g1f3", "a7a8q", "h2h1n", "e1g1"] {
+            assert_eq!(Move::from_uci(s).unwrap().to_uci(), s);
+        }
+        assert_eq!(Move::NULL.to_uci(), "0000");
+        assert!(Move::NULL.is_null());
+    }
+
+    #[test]
+    fn rejects_bad_uci() {
+        for s in ["", "e2", "e2e9", "i2e4", "e7e8k", "e2e4qq", "é2e4"] {
+            assert_eq!(Move::from_uci(s), None, "{}", s);
+        }
     }
 }
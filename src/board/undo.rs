@@ -1,4 +1,5 @@
 use crate::board::piece::{Color, Piece};
+use crate::eval::state::EvalState;
 
 #[derive(Copy, Clone)]
 pub struct Undo {
@@ -6,4 +7,7 @@ pub struct Undo {
     pub side_to_move: Color,
     pub castling_rights: u8,
     pub en_passant_square: Option<u8>,
+    pub halfmove_clock: u16,
+    pub hash: u64,
+    pub eval: EvalState,
 }
\ No newline at end of file
@@ -0,0 +1,106 @@
+use crate::board::piece::{Color, Piece};
+
+pub struct ZobristKeys {
+    pieces: [[[u64; 64]; 6]; 2], // [color][piece][square]
+    side_to_move: u64,
+    castling: [u64; 16],
+    en_passant: [u64; 8], // by file
+}
+
+/// SplitMix64, used to fill the key tables at compile time.
+const fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+const fn generate_keys() -> ZobristKeys {
+    let mut state = 0x2545F4914F6CDD1D;
+    let mut keys = ZobristKeys {
+        pieces: [[[0; 64]; 6]; 2],
+        side_to_move: 0,
+        castling: [0; 16],
+        en_passant: [0; 8],
+    };
+
+    let mut c = 0;
+    while c < 2 {
+        let mut p = 0;
+        while p < 6 {
+            let mut sq = 0;
+            while sq < 64 {
+                keys.pieces[c][p][sq] = splitmix64(&mut state);
+                sq += 1;
+            }
+            p += 1;
+        }
+        c += 1;
+    }
+
+    keys.side_to_move = splitmix64(&mut state);
+
+    // Castling keys are indexed by the full rights mask, with no rights hashing to 0
+    let mut i = 1;
+    while i < 16 {
+        keys.castling[i] = splitmix64(&mut state);
+        i += 1;
+    }
+
+    let mut f = 0;
+    while f < 8 {
+        keys.en_passant[f] = splitmix64(&mut state);
+        f += 1;
+    }
+
+    keys
+}
+
+static KEYS: ZobristKeys = generate_keys();
+
+#[inline(always)]
+pub fn piece_key(color: Color, piece: Piece, square: u8) -> u64 {
+    KEYS.pieces[color.index()][piece.index()][square as usize]
+}
+
+#[inline(always)]
+pub fn side_key() -> u64 {
+    KEYS.side_to_move
+}
+
+#[inline(always)]
+pub fn castling_key(rights: u8) -> u64 {
+    KEYS.castling[rights as usize & 0xF]
+}
+
+#[inline(always)]
+pub fn en_passant_key(file: u8) -> u64 {
+    KEYS.en_passant[file as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn keys_are_unique() {
+        let mut seen = HashSet::new();
+        for c in 0..2 {
+            for p in 0..6 {
+                for sq in 0..64 {
+                    assert!(seen.insert(KEYS.pieces[c][p][sq]));
+                }
+            }
+        }
+        assert!(seen.insert(KEYS.side_to_move));
+        for key in &KEYS.castling[1..] {
+            assert!(seen.insert(*key));
+        }
+        for key in &KEYS.en_passant {
+            assert!(seen.insert(*key));
+        }
+        assert_eq!(castling_key(0), 0);
+    }
+}
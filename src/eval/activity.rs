@@ -0,0 +1,204 @@
+use crate::board::bitboard::*;
+use crate::board::board::Board;
+use crate::board::piece::{Color, Piece};
+use crate::eval::score::Score;
+use crate::movegen::attacks::{bishop_attacks, knight_attacks, queen_attacks, rook_attacks};
+
+// Per safe square above or below a typical count for the piece
+const KNIGHT_MOBILITY: Score = Score::new(4, 4);
+const BISHOP_MOBILITY: Score = Score::new(5, 5);
+const ROOK_MOBILITY: Score = Score::new(2, 4);
+const QUEEN_MOBILITY: Score = Score::new(1, 2);
+const KNIGHT_TYPICAL_MOBILITY: i32 = 4;
+const BISHOP_TYPICAL_MOBILITY: i32 = 6;
+const ROOK_TYPICAL_MOBILITY: i32 = 6;
+const QUEEN_TYPICAL_MOBILITY: i32 = 12;
+
+const BISHOP_PAIR: Score = Score::new(30, 50);
+const ROOK_OPEN_FILE: Score = Score::new(25, 10);
+const ROOK_SEMI_OPEN_FILE: Score = Score::new(12, 6);
+const ROOK_ON_SEVENTH: Score = Score::new(15, 25);
+const KNIGHT_OUTPOST: Score = Score::new(20, 10);
+
+/// A bishop on a7 or h7 shut in by a pawn on b6 or g6, or the same from
+/// Black's side.
+const TRAPPED_BISHOP: Score = Score::new(-100, -80);
+
+/// A rook hemmed in on the back rank by its own uncastled king.
+const TRAPPED_ROOK: Score = Score::new(-45, -5);
+
+/// Mobility and placement of the pieces, from White's point of view.
+pub fn piece_activity(board: &Board) -> Score {
+    side_activity(board, Color::White) - side_activity(board, Color::Black)
+}
+
+fn side_activity(board: &Board, color: Color) -> Score {
+    let pieces = &board.pieces[color.index()];
+    let own_pawns = pieces[Piece::Pawn.index()];
+    let enemy_pawns = board.pieces[color.opposite().index()][Piece::Pawn.index()];
+    let enemy_king = board.pieces[color.opposite().index()][Piece::King.index()];
+    let pawn_defended = pawn_attacks(color, own_pawns);
+
+    // Squares guarded by enemy pawns are not worth counting as mobility
+    let safe = !board.occupied_by(color) & !pawn_attacks(color.opposite(), enemy_pawns);
+    let occupied = board.occupied;
+
+    let mut score = Score::ZERO;
+    let mobility = |attacks: Bitboard, weight: Score, typical: i32| {
+        weight * ((attacks & safe).count_ones() as i32 - typical)
+    };
+
+    let mut knights = pieces[Piece::Knight.index()];
+    while knights != 0 {
+        let sq = pop_lsb(&mut knights);
+        score += mobility(knight_attacks(sq), KNIGHT_MOBILITY, KNIGHT_TYPICAL_MOBILITY);
+
+        // Defended by a pawn and out of reach of enemy pawns for good
+        let rank = relative_rank(color, sq);
+        let attackable = enemy_pawns & forward_ranks(color, sq) & adjacent_files(file_of(sq)) != 0;
+        if (3..=5).contains(&rank) && pawn_defended & bb(sq) != 0 && !attackable {
+            score += KNIGHT_OUTPOST;
+        }
+    }
+
+    let mut bishops = pieces[Piece::Bishop.index()];
+    if bishops.count_ones() >= 2 {
+        score += BISHOP_PAIR;
+    }
+    while bishops != 0 {
+        let sq = pop_lsb(&mut bishops);
+        score += mobility(bishop_attacks(sq, occupied), BISHOP_MOBILITY, BISHOP_TYPICAL_MOBILITY);
+        if is_trapped_bishop(color, sq, enemy_pawns) {
+            score += TRAPPED_BISHOP;
+        }
+    }
+
+    let king = pieces[Piece::King.index()].trailing_zeros() as u8;
+    let mut rooks = pieces[Piece::Rook.index()];
+    while rooks != 0 {
+        let sq = pop_lsb(&mut rooks);
+        let attacks = rook_attacks(sq, occupied);
+        score += mobility(attacks, ROOK_MOBILITY, ROOK_TYPICAL_MOBILITY);
+
+        let file = file_mask(file_of(sq));
+        if file & own_pawns == 0 {
+            score += if file & enemy_pawns == 0 { ROOK_OPEN_FILE } else { ROOK_SEMI_OPEN_FILE };
+        }
+
+        // The seventh only matters while it holds pawns or cuts off the king
+        let (seventh, eighth) = match color {
+            Color::White => (RANK_7, RANK_8),
+            Color::Black => (RANK_2, RANK_1),
+        };
+        if seventh & bb(sq) != 0 && (enemy_pawns & seventh != 0 || enemy_king & eighth != 0) {
+            score += ROOK_ON_SEVENTH;
+        }
+
+        if is_trapped_rook(board, color, sq, king, attacks) {
+            score += TRAPPED_ROOK;
+        }
+    }
+
+    let mut queens = pieces[Piece::Queen.index()];
+    while queens != 0 {
+        let sq = pop_lsb(&mut queens);
+        score += mobility(queen_attacks(sq, occupied), QUEEN_MOBILITY, QUEEN_TYPICAL_MOBILITY);
+    }
+
+    score
+}
+
+fn is_trapped_bishop(color: Color, sq: u8, enemy_pawns: Bitboard) -> bool {
+    // (bishop, blocking pawn) for White; Black's squares are mirrored
+    let traps = [(Square::A7, Square::B6), (Square::H7, Square::G6)];
+    traps.iter().any(|&(bishop, pawn)| {
+        let (bishop, pawn) = match color {
+            Color::White => (bishop as u8, pawn as u8),
+            Color::Black => (bishop as u8 ^ 56, pawn as u8 ^ 56),
+        };
+        sq == bishop && enemy_pawns & bb(pawn) != 0
+    })
+}
+
+/// A rook on the back rank with almost no moves, boxed in by a king that
+/// has walked towards it instead of castling.
+fn is_trapped_rook(board: &Board, color: Color, sq: u8, king: u8, attacks: Bitboard) -> bool {
+    let rights = match color {
+        Color::White => CASTLE_WHITE_KING | CASTLE_WHITE_QUEEN,
+        Color::Black => CASTLE_BLACK_KING | CASTLE_BLACK_QUEEN,
+    };
+    if board.castling_rights & rights != 0 || relative_rank(color, sq) != 0 || rank_of(king) != rank_of(sq) {
+        return false;
+    }
+    if (attacks & !board.occupied_by(color)).count_ones() > 3 {
+        return false;
+    }
+
+    let (king_file, rook_file) = (file_of(king), file_of(sq));
+    (king_file >= 5 && rook_file > king_file) || (king_file <= 2 && rook_file < king_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(fen: &str) -> Score {
+        piece_activity(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn centralised_pieces_are_more_mobile() {
+        let corner = activity("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        let centre = activity("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        assert!(centre.mg > corner.mg && centre.eg > corner.eg);
+
+        // Squares covered by enemy pawns do not count
+        let guarded = activity("4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1");
+        assert_eq!(centre - guarded, KNIGHT_MOBILITY * 2);
+    }
+
+    #[test]
+    fn rewards_bishop_pair() {
+        let pair = activity("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let two_knights = activity("4k3/8/8/8/8/8/8/2N1KN2 w - - 0 1");
+        let both = side_activity(&Board::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap(), Color::White);
+        let one = side_activity(&Board::from_fen("4k3/8/8/8/8/8/8/4KB2 w - - 0 1").unwrap(), Color::White);
+        assert!(pair.mg > two_knights.mg);
+        assert!((both - one).eg >= BISHOP_PAIR.eg);
+    }
+
+    #[test]
+    fn rooks_like_open_files_and_the_seventh() {
+        let closed = activity("4k3/p7/8/8/8/8/P7/R3K3 w - - 0 1");
+        let semi_open = activity("4k3/p7/8/8/8/8/1P6/R3K3 w - - 0 1");
+        let open = activity("4k3/1p6/8/8/8/8/1P6/R3K3 w - - 0 1");
+        assert!(open.mg > semi_open.mg && semi_open.mg > closed.mg);
+
+        let seventh = side_activity(&Board::from_fen("4k3/R7/8/8/8/8/8/4K3 w - - 0 1").unwrap(), Color::White);
+        let sixth = side_activity(&Board::from_fen("4k3/8/R7/8/8/8/8/4K3 w - - 0 1").unwrap(), Color::White);
+        assert_eq!(seventh - sixth, ROOK_ON_SEVENTH);
+    }
+
+    #[test]
+    fn knight_outposts_need_pawn_support() {
+        let outpost = activity("4k3/8/4p3/4N3/3P4/8/8/4K3 w - - 0 1");
+        let unsupported = activity("4k3/8/4p3/4N3/8/2P5/8/4K3 w - - 0 1");
+        let chased = activity("4k3/5p2/4p3/4N3/3P4/8/8/4K3 w - - 0 1");
+        assert!(outpost.mg > unsupported.mg);
+        assert!(outpost.mg > chased.mg);
+    }
+
+    #[test]
+    fn trapped_pieces() {
+        let trapped = side_activity(&Board::from_fen("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1").unwrap(), Color::White);
+        let free = side_activity(&Board::from_fen("4k3/B7/2p5/8/8/8/8/4K3 w - - 0 1").unwrap(), Color::White);
+        assert!((free - trapped).mg >= -TRAPPED_BISHOP.mg);
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/5PPP/5K1R w - - 0 1").unwrap();
+        let king = board.pieces[0][Piece::King.index()].trailing_zeros() as u8;
+        assert!(is_trapped_rook(&board, Color::White, Square::H1 as u8, king, rook_attacks(Square::H1 as u8, board.occupied)));
+        let castled = Board::from_fen("4k3/8/8/8/8/8/5PPP/5RK1 w - - 0 1").unwrap();
+        let king = castled.pieces[0][Piece::King.index()].trailing_zeros() as u8;
+        assert!(!is_trapped_rook(&castled, Color::White, Square::F1 as u8, king, rook_attacks(Square::F1 as u8, castled.occupied)));
+    }
+}
@@ -1,5 +1,9 @@
 use crate::board::board::Board;
 use crate::board::piece::{Piece, Color};
+use crate::eval::activity::piece_activity;
+use crate::eval::king::king_safety;
+use crate::eval::pawns::{passed_pawns, PawnTable};
+use crate::eval::score::MAX_PHASE;
 
 const PAWN: i32 = 100;
 const KNIGHT: i32 = 320;
@@ -7,24 +11,115 @@ const BISHOP: i32 = 330;
 const ROOK: i32 = 500;
 const QUEEN: i32 = 900;
 
-pub fn evaluate(board: &Board) -> i32{
-    let mut score = 0;
+/// How much each piece counts towards the game phase, by `Piece::index()`.
+/// The starting position adds up to `MAX_PHASE`.
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
 
-    for color in [Color::White, Color::Black] {
-        let sign = if color == board.side_to_move { 1 } else { -1 };
-
-        score += sign * material_score(board, color);
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => PAWN,
+        Piece::Knight => KNIGHT,
+        Piece::Bishop => BISHOP,
+        Piece::Rook => ROOK,
+        Piece::Queen => QUEEN,
+        Piece::King => 0,
     }
+}
 
-    score
+pub fn phase_weight(piece: Piece) -> i32 {
+    PHASE_WEIGHTS[piece.index()]
 }
 
-fn material_score(board: &Board, color: Color) -> i32 {
-    let pieces = &board.pieces[color.index()];
+/// Static evaluation from the side to move's point of view. Pawn structure
+/// is looked up in, and stored to, `pawns`.
+pub fn evaluate(board: &Board, pawns: &mut PawnTable) -> i32 {
+    let state = &board.eval;
+    let material = state.material[Color::White.index()] - state.material[Color::Black.index()];
+    let mut score = state.psqt[Color::White.index()] - state.psqt[Color::Black.index()];
+
+    let pawn_entry = pawns.probe(board);
+    score += pawn_entry.score + passed_pawns(board, &pawn_entry);
+    score += piece_activity(board);
+    score += king_safety(board);
+
+    let white = material + score.taper(game_phase(board));
+
+    if board.side_to_move == Color::White { white } else { -white }
+}
+
+/// `MAX_PHASE` with all pieces on the board, 0 with only kings and pawns.
+/// Early promotions can push the sum past the maximum, so it is capped.
+pub fn game_phase(board: &Board) -> i32 {
+    board.eval.phase.min(MAX_PHASE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::score::Score;
+
+    /// The same position with colors swapped and the board flipped top to
+    /// bottom.
+    fn mirror_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let swap_case = |s: &str| -> String {
+            s.chars()
+                .map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() })
+                .collect()
+        };
+
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+        castling.sort_by_key(|c| (c.is_ascii_lowercase(), *c != 'K' && *c != 'k'));
+        let castling: String = castling.into_iter().collect();
+        let en_passant = match fields[3].as_bytes() {
+            [file, rank] => format!("{}{}", *file as char, (b'1' + b'8' - rank) as char),
+            _ => "-".to_string(),
+        };
 
-    pieces[Piece::Pawn.index()].count_ones() as i32 * PAWN +
-    pieces[Piece::Knight.index()].count_ones() as i32 * KNIGHT +
-    pieces[Piece::Bishop.index()].count_ones() as i32 * BISHOP +
-    pieces[Piece::Rook.index()].count_ones() as i32 * ROOK +
-    pieces[Piece::Queen.index()].count_ones() as i32 * QUEEN
-}
\ No newline at end of file
+        format!("{} {} {} {} {}", placement.join("/"), side, castling, en_passant, fields[4..].join(" "))
+    }
+
+    #[test]
+    fn start_position_is_balanced() {
+        let board = Board::startpos();
+        assert_eq!(game_phase(&board), MAX_PHASE);
+        assert_eq!(evaluate(&board, &mut PawnTable::new()), 0);
+        // Term by term too, so two lopsided terms cannot cancel out
+        assert_eq!(piece_activity(&board), Score::ZERO);
+        assert_eq!(king_safety(&board), Score::ZERO);
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "8/8/8/3Pp3/8/4k3/8/3K4 w - e6 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror_fen(fen)).unwrap();
+            assert_eq!(evaluate(&board, &mut PawnTable::new()), evaluate(&mirrored, &mut PawnTable::new()), "{}", fen);
+            assert_eq!(game_phase(&board), game_phase(&mirrored));
+        }
+    }
+
+    #[test]
+    fn phase_follows_material() {
+        let endgame = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&endgame), 4);
+
+        let pawns = Board::from_fen("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(game_phase(&pawns), 0);
+    }
+
+    #[test]
+    fn prefers_developed_pieces() {
+        // Scores are relative to Black, who is yet to move a piece
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1").unwrap();
+        assert!(evaluate(&board, &mut PawnTable::new()) < 0);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::board::bitboard::*;
+use crate::board::board::Board;
+use crate::board::piece::{Color, Piece};
+use crate::eval::score::Score;
+use crate::movegen::attacks::{bishop_attacks, king_attacks, knight_attacks, rook_attacks};
+
+// Danger units, by `Piece::index()`, for every king zone square a piece
+// attacks and for every safe check it has
+const ZONE_ATTACK_WEIGHTS: [i32; 6] = [0, 2, 2, 3, 5, 0];
+const SAFE_CHECK_WEIGHTS: [i32; 6] = [0, 4, 2, 3, 5, 0];
+
+/// Danger units per file in front of the king without a shield pawn, so a
+/// broken shield makes every attack count for more.
+const SHIELD_HOLE_DANGER: i32 = 2;
+
+const SHIELD_CLOSE: Score = Score::new(15, 0);
+const SHIELD_FAR: Score = Score::new(7, 0);
+const SHIELD_HOLE: Score = Score::new(-15, 0);
+const STORM_CLOSE: Score = Score::new(-20, 0);
+const STORM_FAR: Score = Score::new(-8, 0);
+const KING_OPEN_FILE: Score = Score::new(-25, 0);
+const KING_SEMI_OPEN_FILE: Score = Score::new(-10, 0);
+
+const MAX_DANGER: usize = 64;
+
+/// Middlegame penalty by danger units. It grows with the square of the
+/// units, since attackers working together are worth far more than the
+/// sum of their parts, and levels off so it cannot outweigh material.
+static KING_DANGER: [i32; MAX_DANGER] = danger_table();
+
+const fn danger_table() -> [i32; MAX_DANGER] {
+    let mut table = [0; MAX_DANGER];
+    let mut units = 0;
+    while units < MAX_DANGER {
+        let danger = (units * units * 2 / 3) as i32;
+        table[units] = if danger < 500 { danger } else { 500 };
+        units += 1;
+    }
+    table
+}
+
+/// Safety of both kings, from White's point of view.
+pub fn king_safety(board: &Board) -> Score {
+    side_safety(board, Color::White) - side_safety(board, Color::Black)
+}
+
+/// Safety of `color`'s king: its pawn cover, and how hard the enemy pieces
+/// are pressing on it.
+fn side_safety(board: &Board, color: Color) -> Score {
+    let king = board.pieces[color.index()][Piece::King.index()].trailing_zeros() as u8;
+    let (score, holes) = pawn_cover(board, color, king);
+    score - danger(board, color, king, holes)
+}
+
+/// Shield and storm pawns and open files on the king's file and the two
+/// next to it. Also returns the number of files without a shield pawn.
+fn pawn_cover(board: &Board, color: Color, king: u8) -> (Score, i32) {
+    let own_pawns = board.pieces[color.index()][Piece::Pawn.index()];
+    let enemy_pawns = board.pieces[color.opposite().index()][Piece::Pawn.index()];
+    let front = forward_ranks(color, king);
+    let king_file = file_of(king);
+
+    let mut score = Score::ZERO;
+    let mut holes = 0;
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let mask = file_mask(file);
+
+        match nearest_rank_distance(color, king, own_pawns & front & mask) {
+            Some(1) => score += SHIELD_CLOSE,
+            Some(2) => score += SHIELD_FAR,
+            _ => {
+                score += SHIELD_HOLE;
+                holes += 1;
+            }
+        }
+
+        match nearest_rank_distance(color, king, enemy_pawns & front & mask) {
+            Some(1..=2) => score += STORM_CLOSE,
+            Some(3) => score += STORM_FAR,
+            _ => {}
+        }
+
+        if own_pawns & mask == 0 {
+            score += if enemy_pawns & mask == 0 { KING_OPEN_FILE } else { KING_SEMI_OPEN_FILE };
+        }
+    }
+
+    (score, holes)
+}
+
+/// Ranks between the king and the closest of `pawns`, all of which must be
+/// in front of it.
+fn nearest_rank_distance(color: Color, king: u8, pawns: Bitboard) -> Option<u8> {
+    if pawns == 0 {
+        return None;
+    }
+    let nearest = match color {
+        Color::White => pawns.trailing_zeros(),
+        Color::Black => 63 - pawns.leading_zeros(),
+    } as u8;
+    Some(rank_of(nearest).abs_diff(rank_of(king)))
+}
+
+/// The squares around the king plus three more in front, where an attack is
+/// most dangerous.
+fn king_zone(color: Color, king: u8) -> Bitboard {
+    let around = king_attacks(king) | bb(king);
+    let ahead = match color {
+        Color::White => around << 8,
+        Color::Black => around >> 8,
+    };
+    around | ahead
+}
+
+/// Penalty for the attack on `color`'s king, looked up in the danger table.
+/// A lone attacker is rarely a threat, so at least two are needed.
+fn danger(board: &Board, color: Color, king: u8, holes: i32) -> Score {
+    let them = color.opposite();
+    let enemy = &board.pieces[them.index()];
+    let occupied = board.occupied;
+    let zone = king_zone(color, king);
+
+    // Checking squares the defender does not cover with a pawn or the king;
+    // cheaper than full attack maps and close enough
+    let own_pawns = board.pieces[color.index()][Piece::Pawn.index()];
+    let safe = !board.occupied_by(them) & !pawn_attacks(color, own_pawns) & !king_attacks(king);
+    let knight_checks = knight_attacks(king) & safe;
+    let bishop_checks = bishop_attacks(king, occupied) & safe;
+    let rook_checks = rook_attacks(king, occupied) & safe;
+
+    let mut attackers = 0;
+    let mut units = 0;
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let mut pieces = enemy[piece.index()];
+        while pieces != 0 {
+            let sq = pop_lsb(&mut pieces);
+            let (attacks, checks) = match piece {
+                Piece::Knight => (knight_attacks(sq), knight_checks),
+                Piece::Bishop => (bishop_attacks(sq, occupied), bishop_checks),
+                Piece::Rook => (rook_attacks(sq, occupied), rook_checks),
+                _ => {
+                    let attacks = bishop_attacks(sq, occupied) | rook_attacks(sq, occupied);
+                    (attacks, bishop_checks | rook_checks)
+                }
+            };
+
+            if attacks & zone != 0 {
+                attackers += 1;
+                units += ZONE_ATTACK_WEIGHTS[piece.index()] * (attacks & zone).count_ones() as i32;
+            }
+            units += SAFE_CHECK_WEIGHTS[piece.index()] * (attacks & checks).count_ones() as i32;
+        }
+    }
+
+    if attackers < 2 {
+        return Score::ZERO;
+    }
+
+    units += holes * SHIELD_HOLE_DANGER;
+    // Only a middlegame term; with the queens gone the king should come out
+    Score::new(KING_DANGER[(units as usize).min(MAX_DANGER - 1)], 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn safety(fen: &str, color: Color) -> Score {
+        side_safety(&Board::from_fen(fen).unwrap(), color)
+    }
+
+    #[test]
+    fn danger_grows_faster_than_attacks() {
+        assert_eq!(KING_DANGER[0], 0);
+        assert!(KING_DANGER[20] > 2 * KING_DANGER[10]);
+        assert!(KING_DANGER.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn shield_pawns_protect_the_king() {
+        let intact = safety("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1", Color::White);
+        let advanced = safety("6k1/8/8/8/8/6P1/5P1P/6K1 w - - 0 1", Color::White);
+        let broken = safety("6k1/8/8/8/8/8/5P1P/6K1 w - - 0 1", Color::White);
+        assert!(intact.mg > advanced.mg && advanced.mg > broken.mg);
+    }
+
+    #[test]
+    fn storming_pawns_and_open_files_hurt() {
+        let calm = safety("6k1/p7/8/8/8/8/5PPP/6K1 w - - 0 1", Color::White);
+        let storm = safety("6k1/8/8/8/6p1/8/5PPP/6K1 w - - 0 1", Color::White);
+        assert!(calm.mg > storm.mg);
+
+        let semi_open = safety("6k1/6p1/8/8/8/8/5P1P/6K1 w - - 0 1", Color::White);
+        let open = safety("6k1/p7/8/8/8/8/5P1P/6K1 w - - 0 1", Color::White);
+        assert_eq!(semi_open - open, KING_SEMI_OPEN_FILE - KING_OPEN_FILE);
+    }
+
+    #[test]
+    fn coordinated_attackers_are_dangerous() {
+        // A queen on h3 alone is not an attack yet; with a knight joining in
+        // it is
+        let intact = safety("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1", Color::White);
+        let queen = safety("6k1/8/8/8/8/7q/5PPP/6K1 w - - 0 1", Color::White);
+        let queen_knight = safety("6k1/8/8/8/4n3/7q/5PPP/6K1 w - - 0 1", Color::White);
+        assert_eq!(queen, intact);
+        assert!(queen_knight.mg < queen.mg);
+    }
+
+    #[test]
+    fn safe_checks_add_danger() {
+        // From c3 the knight can check on e2; from b3 it cannot. Neither
+        // square touches the king zone, which the queen and bishop attack.
+        let no_check = safety("6k1/8/8/8/3b4/1n5q/5PPP/6K1 w - - 0 1", Color::White);
+        let check = safety("6k1/8/8/8/3b4/2n4q/5PPP/6K1 w - - 0 1", Color::White);
+        assert!(no_check.mg > check.mg);
+    }
+}
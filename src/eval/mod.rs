@@ -1 +1,8 @@
-pub mod eval;
\ No newline at end of file
+pub mod activity;
+#[allow(clippy::module_inception)]
+pub mod eval;
+pub mod king;
+pub mod pawns;
+pub mod pst;
+pub mod score;
+pub mod state;
@@ -0,0 +1,223 @@
+use crate::board::bitboard::*;
+use crate::board::board::Board;
+use crate::board::piece::{Color, Piece};
+use crate::eval::score::Score;
+
+/// Entries in the pawn hash table; must be a power of two.
+pub const PAWN_TABLE_ENTRIES: usize = 1 << 14;
+
+const DOUBLED: Score = Score::new(-10, -25);
+const ISOLATED: Score = Score::new(-6, -15);
+const BACKWARD: Score = Score::new(-8, -12);
+
+// Indexed by rank from the pawn's own side
+#[rustfmt::skip]
+const CONNECTED: [Score; 8] = [
+    Score::new(0, 0), Score::new(3, 0), Score::new(5, 3), Score::new(8, 6),
+    Score::new(15, 12), Score::new(30, 25), Score::new(55, 50), Score::new(0, 0),
+];
+#[rustfmt::skip]
+const PASSED: [Score; 8] = [
+    Score::new(0, 0), Score::new(2, 5), Score::new(2, 8), Score::new(5, 15),
+    Score::new(15, 30), Score::new(30, 55), Score::new(50, 90), Score::new(0, 0),
+];
+
+/// Endgame bonus for a passed pawn with nothing between it and promotion.
+const PASSED_FREE_PATH: [i32; 8] = [0, 0, 0, 5, 10, 20, 35, 0];
+
+/// Endgame bonus per square between the enemy king and a passed pawn's stop
+/// square, and penalty per square for our own king, for each rank the pawn
+/// has advanced past the third.
+const PASSED_ENEMY_KING_DISTANCE: i32 = 5;
+const PASSED_OWN_KING_DISTANCE: i32 = 2;
+
+/// Pawn structure terms that depend on nothing but the pawns.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PawnEntry {
+    key: u64,
+    pub score: Score,          // White's view
+    pub passed: [Bitboard; 2], // [color]
+}
+
+/// Caches pawn structure by the pawn-only Zobrist key. Pawns move rarely,
+/// so most probes hit even with a small table.
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        PawnTable::new()
+    }
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        PawnTable { entries: vec![PawnEntry::default(); PAWN_TABLE_ENTRIES] }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+
+    /// The pawn structure of `board`, evaluated on a miss. Empty entries
+    /// carry key 0, which is also the key of a board without pawns; a zeroed
+    /// entry is the right answer there anyway.
+    pub fn probe(&mut self, board: &Board) -> PawnEntry {
+        let key = board.eval.pawn_key;
+        let entry = &mut self.entries[key as usize & (PAWN_TABLE_ENTRIES - 1)];
+        if entry.key != key {
+            let (white, white_passed) = pawn_structure(board, Color::White);
+            let (black, black_passed) = pawn_structure(board, Color::Black);
+            *entry = PawnEntry { key, score: white - black, passed: [white_passed, black_passed] };
+        }
+        *entry
+    }
+}
+
+#[inline]
+fn stop_square(color: Color, sq: u8) -> u8 {
+    match color {
+        Color::White => sq + 8,
+        Color::Black => sq - 8,
+    }
+}
+
+/// Score `color`'s pawns and find the passed ones.
+fn pawn_structure(board: &Board, color: Color) -> (Score, Bitboard) {
+    let own = board.pieces[color.index()][Piece::Pawn.index()];
+    let enemy = board.pieces[color.opposite().index()][Piece::Pawn.index()];
+    let supported = pawn_attacks(color, own);
+    let enemy_attacks = pawn_attacks(color.opposite(), enemy);
+
+    let mut score = Score::ZERO;
+    let mut passed = 0;
+    let mut pawns = own;
+    while pawns != 0 {
+        let sq = pop_lsb(&mut pawns);
+        let rank = relative_rank(color, sq) as usize;
+        let file = file_mask(file_of(sq));
+        let neighbours = adjacent_files(file_of(sq));
+        let front = forward_ranks(color, sq);
+
+        let doubled = own & front & file != 0;
+        if doubled {
+            score += DOUBLED;
+        }
+
+        // A backward pawn has fallen behind its neighbours, so none can come
+        // to its support, and cannot advance safely either
+        if own & neighbours == 0 {
+            score += ISOLATED;
+        } else if own & neighbours & !front == 0 && enemy_attacks & bb(stop_square(color, sq)) != 0 {
+            score += BACKWARD;
+        }
+
+        let phalanx = own & neighbours & (RANK_1 << (rank_of(sq) * 8)) != 0;
+        if phalanx || supported & bb(sq) != 0 {
+            score += CONNECTED[rank];
+        }
+
+        // Only the front pawn of a doubled pair counts as passed
+        if !doubled && enemy & front & (file | neighbours) == 0 {
+            passed |= bb(sq);
+            score += PASSED[rank];
+        }
+    }
+
+    (score, passed)
+}
+
+/// Passed pawn terms that depend on the other pieces, so cannot be cached:
+/// a free path to promotion and the distance of both kings, from White's view.
+pub fn passed_pawns(board: &Board, entry: &PawnEntry) -> Score {
+    let mut score = Score::ZERO;
+    for color in [Color::White, Color::Black] {
+        let own_king = board.pieces[color.index()][Piece::King.index()].trailing_zeros() as u8;
+        let enemy_king = board.pieces[color.opposite().index()][Piece::King.index()].trailing_zeros() as u8;
+        let sign = if color == Color::White { 1 } else { -1 };
+
+        let mut passed = entry.passed[color.index()];
+        while passed != 0 {
+            let sq = pop_lsb(&mut passed);
+            let rank = relative_rank(color, sq) as usize;
+            let mut eg = 0;
+
+            if forward_ranks(color, sq) & file_mask(file_of(sq)) & board.occupied == 0 {
+                eg += PASSED_FREE_PATH[rank];
+            }
+
+            let stop = stop_square(color, sq);
+            let weight = rank.saturating_sub(2) as i32;
+            eg += weight * distance(enemy_king, stop) as i32 * PASSED_ENEMY_KING_DISTANCE;
+            eg -= weight * distance(own_king, stop) as i32 * PASSED_OWN_KING_DISTANCE;
+
+            score += Score::new(0, eg * sign);
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::bitboard::Square;
+
+    fn structure(fen: &str, color: Color) -> (Score, Bitboard) {
+        pawn_structure(&Board::from_fen(fen).unwrap(), color)
+    }
+
+    #[test]
+    fn doubled_and_isolated() {
+        // Both c-pawns are isolated, the rear one doubled; only the front one is passed
+        let (score, passed) = structure("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1", Color::White);
+        assert_eq!(score, DOUBLED + ISOLATED * 2 + PASSED[2]);
+        assert_eq!(passed, bb(Square::C3 as u8));
+    }
+
+    #[test]
+    fn backward_and_connected() {
+        // d3 has no neighbour behind it and c5 guards d4. c4 and e4 are
+        // defended by d3, and e4 has no black pawn in front of it.
+        let (score, passed) = structure("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1", Color::White);
+        assert_eq!(score, BACKWARD + CONNECTED[3] * 2 + PASSED[3]);
+        assert_eq!(passed, bb(Square::E4 as u8));
+    }
+
+    #[test]
+    fn black_pawns_mirror_white() {
+        let (white, white_passed) = structure("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1", Color::White);
+        let (black, black_passed) = structure("4k3/8/3p4/2p1p3/2P5/8/8/4K3 b - - 0 1", Color::Black);
+        assert_eq!(white, black);
+        assert_eq!(white_passed.swap_bytes(), black_passed);
+    }
+
+    #[test]
+    fn passed_pawn_prefers_a_free_path_and_a_far_enemy_king() {
+        let score = |fen: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            passed_pawns(&board, &PawnTable::new().probe(&board)).eg
+        };
+        let free = score("8/8/8/1P6/8/8/8/k3K3 w - - 0 1");
+        let blocked = score("8/1n6/8/1P6/8/8/8/k3K3 w - - 0 1");
+        let king_near = score("8/8/k7/1P6/8/8/8/4K3 w - - 0 1");
+        assert!(free > blocked);
+        assert!(free > king_near);
+    }
+
+    #[test]
+    fn probe_caches_by_pawn_key() {
+        let mut table = PawnTable::new();
+        let board = Board::from_fen("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1").unwrap();
+        let entry = table.probe(&board);
+        assert_eq!(table.probe(&board), entry);
+
+        // Moving a king leaves the pawn key and the entry alone
+        let moved = Board::from_fen("3k4/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(moved.eval.pawn_key, board.eval.pawn_key);
+        assert_eq!(table.probe(&moved), entry);
+
+        table.clear();
+        assert_eq!(table.probe(&board), entry);
+    }
+}
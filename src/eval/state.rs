@@ -0,0 +1,107 @@
+use crate::board::bitboard::pop_lsb;
+use crate::board::board::Board;
+use crate::board::piece::{index_to_piece, Color, Piece};
+use crate::board::zobrist;
+use crate::eval::eval::{phase_weight, piece_value};
+use crate::eval::pst::pst;
+use crate::eval::score::Score;
+
+/// The parts of the evaluation that only depend on where each piece stands.
+/// `Board` keeps them up to date in make_move, so reading them is O(1).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalState {
+    pub material: [i32; 2], // [color]
+    pub psqt: [Score; 2],   // [color], each from its own side's view
+    pub phase: i32,         // uncapped, promotions can push it past MAX_PHASE
+    pub pawn_key: u64,      // Zobrist key of the pawns alone
+}
+
+impl EvalState {
+    /// Sum everything up from scratch.
+    pub fn compute(board: &Board) -> Self {
+        let mut state = EvalState::default();
+        for color in [Color::White, Color::Black] {
+            for (index, &bitboard) in board.pieces[color.index()].iter().enumerate() {
+                let mut bitboard = bitboard;
+                while bitboard != 0 {
+                    state.add(color, index_to_piece(index), pop_lsb(&mut bitboard));
+                }
+            }
+        }
+        state
+    }
+
+    #[inline]
+    pub fn add(&mut self, color: Color, piece: Piece, sq: u8) {
+        self.material[color.index()] += piece_value(piece);
+        self.psqt[color.index()] += pst(color, piece, sq);
+        self.phase += phase_weight(piece);
+        self.toggle_pawn(color, piece, sq);
+    }
+
+    #[inline]
+    pub fn remove(&mut self, color: Color, piece: Piece, sq: u8) {
+        self.material[color.index()] -= piece_value(piece);
+        self.psqt[color.index()] -= pst(color, piece, sq);
+        self.phase -= phase_weight(piece);
+        self.toggle_pawn(color, piece, sq);
+    }
+
+    #[inline]
+    pub fn move_piece(&mut self, color: Color, piece: Piece, from: u8, to: u8) {
+        self.psqt[color.index()] += pst(color, piece, to) - pst(color, piece, from);
+        self.toggle_pawn(color, piece, from);
+        self.toggle_pawn(color, piece, to);
+    }
+
+    #[inline]
+    fn toggle_pawn(&mut self, color: Color, piece: Piece, sq: u8) {
+        if piece == Piece::Pawn {
+            self.pawn_key ^= zobrist::piece_key(color, piece, sq);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::movegen::generate_legal_moves;
+
+    // Walk every line a few plies deep; make_move checks the state against
+    // `compute` in debug builds, this checks it in all builds
+    fn walk(board: &mut Board, depth: u32) {
+        assert_eq!(board.eval, EvalState::compute(board), "{}", board.to_fen());
+        if depth == 0 {
+            return;
+        }
+        for mv in generate_legal_moves(board) {
+            let before = board.eval;
+            let undo = board.make_move(mv);
+            walk(board, depth - 1);
+            board.unmake_move(mv, undo);
+            assert_eq!(board.eval, before);
+        }
+    }
+
+    #[test]
+    fn stays_in_sync_through_special_moves() {
+        for fen in [
+            // Castling both ways, en passant and promotions with capture
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            walk(&mut Board::from_fen(fen).unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn promotion_changes_material_and_phase() {
+        let mut board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mv = *generate_legal_moves(&board).iter().find(|m| m.to_uci() == "b7b8q").unwrap();
+        board.make_move(mv);
+
+        assert_eq!(board.eval.material, [piece_value(Piece::Queen), 0]);
+        assert_eq!(board.eval.phase, phase_weight(Piece::Queen));
+    }
+}
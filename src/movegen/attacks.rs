@@ -1,33 +1,27 @@
 use crate::board::bitboard::{Bitboard, bb, FILE_A, FILE_H};
 
-// Attack lookup tables
-static mut KNIGHT_ATTACKS: [Bitboard; 64] = [0; 64];
-static mut KING_ATTACKS: [Bitboard; 64] = [0; 64];
-static mut WHITE_PAWN_ATTACKS: [Bitboard; 64] = [0; 64];
-static mut BLACK_PAWN_ATTACKS: [Bitboard; 64] = [0; 64];
-
-// Initialization flag
-static mut TABLES_INITIALIZED: bool = false;
-
-/// Initialize all attack tables. Must be called before using lookup functions.
-pub fn init_attack_tables() {
-    unsafe {
-        if TABLES_INITIALIZED {
-            return;
+pub use crate::movegen::magic::{rook_attacks, bishop_attacks};
+
+/// Evaluate a `const fn(u8) -> Bitboard` for every square.
+macro_rules! leaper_table {
+    ($compute:ident) => {{
+        let mut table = [0; 64];
+        let mut square = 0;
+        while square < 64 {
+            table[square] = $compute(square as u8);
+            square += 1;
         }
-
-        for square in 0..64 {
-            KNIGHT_ATTACKS[square] = compute_knight_attacks(square as u8);
-            KING_ATTACKS[square] = compute_king_attacks(square as u8);
-            WHITE_PAWN_ATTACKS[square] = compute_white_pawn_attacks(square as u8);
-            BLACK_PAWN_ATTACKS[square] = compute_black_pawn_attacks(square as u8);
-        }
-
-        TABLES_INITIALIZED = true;
-    }
+        table
+    }};
 }
 
-fn compute_knight_attacks(square: u8) -> Bitboard {
+// Attack lookup tables, built at compile time
+static KNIGHT_ATTACKS: [Bitboard; 64] = leaper_table!(compute_knight_attacks);
+static KING_ATTACKS: [Bitboard; 64] = leaper_table!(compute_king_attacks);
+static WHITE_PAWN_ATTACKS: [Bitboard; 64] = leaper_table!(compute_white_pawn_attacks);
+static BLACK_PAWN_ATTACKS: [Bitboard; 64] = leaper_table!(compute_black_pawn_attacks);
+
+const fn compute_knight_attacks(square: u8) -> Bitboard {
     let b = bb(square);
     let mut attacks = 0;
 
@@ -44,7 +38,7 @@ fn compute_knight_attacks(square: u8) -> Bitboard {
     attacks
 }
 
-fn compute_king_attacks(square: u8) -> Bitboard {
+const fn compute_king_attacks(square: u8) -> Bitboard {
     let b = bb(square);
     let mut attacks = 0;
 
@@ -60,12 +54,12 @@ fn compute_king_attacks(square: u8) -> Bitboard {
     attacks
 }
 
-fn compute_white_pawn_attacks(square: u8) -> Bitboard {
+const fn compute_white_pawn_attacks(square: u8) -> Bitboard {
     let b = bb(square);
     ((b << 7) & !FILE_H) | ((b << 9) & !FILE_A)
 }
 
-fn compute_black_pawn_attacks(square: u8) -> Bitboard {
+const fn compute_black_pawn_attacks(square: u8) -> Bitboard {
     let b = bb(square);
     ((b >> 7) & !FILE_A) | ((b >> 9) & !FILE_H)
 }
@@ -73,25 +67,115 @@ fn compute_black_pawn_attacks(square: u8) -> Bitboard {
 /// Get knight attacks from lookup table
 #[inline]
 pub fn knight_attacks(square: u8) -> Bitboard {
-    unsafe { KNIGHT_ATTACKS[square as usize] }
+    KNIGHT_ATTACKS[square as usize]
 }
 
 /// Get king attacks from lookup table
 #[inline]
 pub fn king_attacks(square: u8) -> Bitboard {
-    unsafe { KING_ATTACKS[square as usize] }
+    KING_ATTACKS[square as usize]
 }
 
 /// Get white pawn attacks from lookup table
 #[inline]
 pub fn white_pawn_attacks(square: u8) -> Bitboard {
-    unsafe { WHITE_PAWN_ATTACKS[square as usize] }
+    WHITE_PAWN_ATTACKS[square as usize]
 }
 
 /// Get black pawn attacks from lookup table
 #[inline]
 pub fn black_pawn_attacks(square: u8) -> Bitboard {
-    unsafe { BLACK_PAWN_ATTACKS[square as usize] }
+    BLACK_PAWN_ATTACKS[square as usize]
+}
+
+#[inline(always)]
+const fn aligned(a: u8, b: u8) -> bool {
+    let df = (b % 8) as i8 - (a % 8) as i8;
+    let dr = (b / 8) as i8 - (a / 8) as i8;
+    a != b && (df == 0 || dr == 0 || df == dr || df == -dr)
+}
+
+/// Unit step from `a` towards `b`, which must be aligned with it.
+#[inline(always)]
+const fn step_towards(a: u8, b: u8) -> (i8, i8) {
+    let df = (b % 8) as i8 - (a % 8) as i8;
+    let dr = (b / 8) as i8 - (a / 8) as i8;
+    (df.signum(), dr.signum())
+}
+
+const fn compute_between(a: u8, b: u8) -> Bitboard {
+    if !aligned(a, b) {
+        return 0;
+    }
+
+    let (df, dr) = step_towards(a, b);
+    let mut file = (a % 8) as i8 + df;
+    let mut rank = (a / 8) as i8 + dr;
+    let mut between = 0;
+
+    while (rank * 8 + file) as u8 != b {
+        between |= bb((rank * 8 + file) as u8);
+        file += df;
+        rank += dr;
+    }
+
+    between
+}
+
+const fn compute_line(a: u8, b: u8) -> Bitboard {
+    if !aligned(a, b) {
+        return 0;
+    }
+
+    let (df, dr) = step_towards(a, b);
+    let mut line = bb(a);
+
+    // Walk from `a` to the edge in both directions
+    let mut dir = 0;
+    while dir < 2 {
+        let (sf, sr) = if dir == 0 { (df, dr) } else { (-df, -dr) };
+        let mut file = (a % 8) as i8 + sf;
+        let mut rank = (a / 8) as i8 + sr;
+        while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+            line |= bb((rank * 8 + file) as u8);
+            file += sf;
+            rank += sr;
+        }
+        dir += 1;
+    }
+
+    line
+}
+
+macro_rules! square_pair_table {
+    ($compute:ident) => {{
+        let mut table = [[0; 64]; 64];
+        let mut a = 0;
+        while a < 64 {
+            let mut b = 0;
+            while b < 64 {
+                table[a][b] = $compute(a as u8, b as u8);
+                b += 1;
+            }
+            a += 1;
+        }
+        table
+    }};
+}
+
+static BETWEEN: [[Bitboard; 64]; 64] = square_pair_table!(compute_between);
+static LINE: [[Bitboard; 64]; 64] = square_pair_table!(compute_line);
+
+/// Squares strictly between `a` and `b`, or empty if they don't share a line
+#[inline]
+pub fn between(a: u8, b: u8) -> Bitboard {
+    BETWEEN[a as usize][b as usize]
+}
+
+/// The full edge-to-edge line through `a` and `b`, or empty if they don't share one
+#[inline]
+pub fn line(a: u8, b: u8) -> Bitboard {
+    LINE[a as usize][b as usize]
 }
 
 #[inline]
@@ -111,7 +195,7 @@ fn sliding_ray(
         }
 
         s += delta;
-        if s < 0 || s >= 64 {
+        if !(0..64).contains(&s) {
             break;
         }
 
@@ -128,10 +212,11 @@ fn sliding_ray(
     attacks
 }
 
-#[inline] fn on_file_a(sq: u8) -> bool { sq % 8 == 0 }
+#[inline] fn on_file_a(sq: u8) -> bool { sq.is_multiple_of(8) }
 #[inline] fn on_file_h(sq: u8) -> bool { sq % 8 == 7 }
 
-pub fn rook_attacks(square: u8, occupied: Bitboard) -> Bitboard {
+/// Reference rook attacks by walking each ray. Used to fill the magic tables.
+pub(crate) fn rook_attacks_slow(square: u8, occupied: Bitboard) -> Bitboard {
     let mut attacks = 0;
 
     attacks |= sliding_ray(square, occupied,  8, |_| false);      // north
@@ -142,7 +227,8 @@ pub fn rook_attacks(square: u8, occupied: Bitboard) -> Bitboard {
     attacks
 }
 
-pub fn bishop_attacks(square: u8, occupied: Bitboard) -> Bitboard {
+/// Reference bishop attacks by walking each ray. Used to fill the magic tables.
+pub(crate) fn bishop_attacks_slow(square: u8, occupied: Bitboard) -> Bitboard {
     let mut attacks = 0;
 
     attacks |= sliding_ray(square, occupied,  9, on_file_h); // NE
@@ -164,25 +250,21 @@ mod tests {
     
     #[test]
     fn knight_center() {
-        init_attack_tables();
         assert_eq!(knight_attacks(Square::E4 as u8).count_ones(), 8);
     }
 
     #[test]
     fn king_center() {
-        init_attack_tables();
         assert_eq!(king_attacks(Square::E4 as u8).count_ones(), 8);
     }
 
     #[test]
     fn white_pawn_center() {
-        init_attack_tables();
         assert_eq!(white_pawn_attacks(Square::E4 as u8).count_ones(), 2);
     }
 
     #[test]
     fn black_pawn_center() {
-        init_attack_tables();
         assert_eq!(black_pawn_attacks(Square::E5 as u8).count_ones(), 2);
     }
 
@@ -250,6 +332,18 @@ mod tests {
         assert!(attacks & bb(Square::G7 as u8) == 0);
     }
 
+    #[test]
+    fn between_and_line() {
+        assert_eq!(between(Square::A1 as u8, Square::D4 as u8), bb(Square::B2 as u8) | bb(Square::C3 as u8));
+        assert_eq!(between(Square::E1 as u8, Square::E2 as u8), 0);
+        assert_eq!(between(Square::A1 as u8, Square::B3 as u8), 0);
+        assert_eq!(between(Square::H8 as u8, Square::H5 as u8), bb(Square::H7 as u8) | bb(Square::H6 as u8));
+
+        assert_eq!(line(Square::C3 as u8, Square::E5 as u8).count_ones(), 8);
+        assert_eq!(line(Square::B1 as u8, Square::E1 as u8), 0xFF);
+        assert_eq!(line(Square::A1 as u8, Square::B3 as u8), 0);
+    }
+
         #[test]
     fn queen_corner_a1() {
         let attacks = queen_attacks(Square::A1 as u8, 0);
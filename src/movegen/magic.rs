@@ -0,0 +1,259 @@
+use std::sync::OnceLock;
+
+use crate::board::bitboard::*;
+use crate::movegen::attacks::{rook_attacks_slow, bishop_attacks_slow};
+
+// Magic multipliers, found offline by random search with a fixed shift of
+// 64 - relevant bits. Any collision they produce maps to identical attacks,
+// which debug builds check as the tables are filled.
+// With the `pext` feature on a BMI2 target the multipliers are unused and
+// the tables are indexed with PEXT instead.
+const ROOK_MAGICS: [u64; 64] = [
+    0x1080004008801020, 0x0840092002C03000, 0x1900200010400900, 0x0880100008000480,
+    0x4200100420080200, 0x8100020100080400, 0x0200040110886200, 0x0200008040220411,
+    0x0404800084400220, 0x0000401000402000, 0x0086001081220440, 0x0408800800100280,
+    0x000A001201040820, 0x8848800200840080, 0x4001000100040200, 0x0442000102105084,
+    0x9080010020804100, 0x0040404000201009, 0x0000808010002009, 0x2200090021D00100,
+    0x0008008008040080, 0x0004004002010040, 0x0011040008015042, 0x00000A0001768104,
+    0x0000800080204009, 0x2010004140002001, 0x9800200280100080, 0x1000100080080080,
+    0x0442000A00049020, 0x2100040080020080, 0x0800120400900148, 0x0010040A00128541,
+    0x2800804000800030, 0x1010002000400041, 0x4000200011004100, 0x0610008410800800,
+    0x0400802402800800, 0xC100020080800400, 0x0002000802000401, 0x0182085882000401,
+    0x0220204000808000, 0x2860100040024022, 0x0001002004110040, 0x99101042000A0020,
+    0x0004080004008080, 0x0010040002008080, 0x2012004881020004, 0x8300842444820011,
+    0x0088403882010200, 0x0820400080210100, 0x0110910040A00300, 0x0801100280080480,
+    0x0242009008200600, 0x1002000489500200, 0x0040800200010080, 0x0091800041000080,
+    0x0000209300488001, 0x04C1002414824001, 0x020020000B001041, 0x7000100004200901,
+    0x8002002004100802, 0x30010002084C0007, 0x0888221800813004, 0x4000002840840112,
+];
+const BISHOP_MAGICS: [u64; 64] = [
+    0xA010041108003100, 0x006082020A002900, 0x6810010619200000, 0x08281A0520000408,
+    0x0001104001000400, 0x0018901008048400, 0x00040A0210245280, 0x000200210808A402,
+    0x9140048410821200, 0x0800091010820041, 0x20504804832202C0, 0x0100091401081000,
+    0x8021011140000012, 0x0810020804450400, 0x208B0542109008A2, 0x0080084A08040204,
+    0x0040E2A80811244C, 0x2505022008008108, 0x0430220100420040, 0x010A040420220040,
+    0x1105000290400000, 0x0093001200822120, 0x4000A62048043004, 0x280120048A015004,
+    0x006090002A020814, 0x44042000240800D0, 0x01102800040A4400, 0x1004080080220040,
+    0x0001001011004024, 0x0010044000805040, 0x0914041200820100, 0x0004821012821480,
+    0x0024040500C05021, 0x0088611002080200, 0x0116080A00040020, 0x4000020080080080,
+    0x2450450140840040, 0x0000880201484100, 0x0222020404020092, 0x8081110600002E00,
+    0x2842101105000801, 0x1100809008001025, 0x00020202221C0400, 0x0422014022009020,
+    0x0210046102100C00, 0xC004008082029102, 0x00AA461801101200, 0x0404080080201108,
+    0x020542108C205002, 0x0410544804100100, 0x0040910841100000, 0x0400200042021100,
+    0x00004204850400C0, 0x0200100410A42102, 0x1040020801210102, 0x0805040410420000,
+    0x2884804130100200, 0x800C262201242000, 0x1058000194108800, 0x0014221054420204,
+    0x0104000012A02200, 0x0200881003300100, 0x0140400202840100, 0x0402020801010201,
+];
+
+#[derive(Copy, Clone, Default)]
+struct Magic {
+    mask: Bitboard,   // relevant occupancy, board edges excluded
+    magic: u64,
+    shift: u32,
+    offset: usize,    // start of this square's slice in the shared table
+}
+
+impl Magic {
+    #[inline(always)]
+    fn index(&self, occupied: Bitboard) -> usize {
+        #[cfg(all(feature = "pext", target_arch = "x86_64", target_feature = "bmi2"))]
+        return self.pext_index(occupied);
+
+        #[cfg(not(all(feature = "pext", target_arch = "x86_64", target_feature = "bmi2")))]
+        return self.magic_index(occupied);
+    }
+
+    #[inline(always)]
+    #[cfg_attr(all(feature = "pext", target_arch = "x86_64", target_feature = "bmi2", not(test)), allow(dead_code))]
+    fn magic_index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+
+    /// BMI2 gathers the masked occupancy bits into a dense index directly.
+    #[inline(always)]
+    #[cfg(all(feature = "pext", target_arch = "x86_64", target_feature = "bmi2"))]
+    fn pext_index(&self, occupied: Bitboard) -> usize {
+        // SAFETY: this is only compiled when BMI2 is enabled for the target
+        self.offset + unsafe { std::arch::x86_64::_pext_u64(occupied, self.mask) } as usize
+    }
+}
+
+struct SliderTables {
+    rook: [Magic; 64],
+    bishop: [Magic; 64],
+    attacks: Vec<Bitboard>,
+}
+
+static TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+/// Squares whose occupancy can change a slider's attacks from `square`.
+/// Edge squares never block anything beyond themselves, so they are dropped.
+fn relevance_mask(square: u8, empty_board_attacks: Bitboard) -> Bitboard {
+    let rank_mask = RANK_1 << (8 * rank_of(square));
+    let file_mask = FILE_A << file_of(square);
+    let edges = ((RANK_1 | RANK_8) & !rank_mask) | ((FILE_A | FILE_H) & !file_mask);
+
+    empty_board_attacks & !edges
+}
+
+fn fill_slider(
+    entries: &mut [Magic; 64],
+    attacks: &mut Vec<Bitboard>,
+    magics: &[u64; 64],
+    slow: fn(u8, Bitboard) -> Bitboard,
+    index: fn(&Magic, Bitboard) -> usize,
+) {
+    for square in 0..64u8 {
+        let mask = relevance_mask(square, slow(square, 0));
+        let bits = mask.count_ones();
+        let entry = Magic {
+            mask,
+            magic: magics[square as usize],
+            shift: 64 - bits,
+            offset: attacks.len(),
+        };
+        attacks.resize(attacks.len() + (1 << bits), 0);
+
+        // Carry-rippler walk over every subset of the mask
+        let mut subset: Bitboard = 0;
+        loop {
+            // A slider always attacks at least one square, so 0 marks an
+            // unwritten slot; anything else must be the same attack set
+            let slot = &mut attacks[index(&entry, subset)];
+            let value = slow(square, subset);
+            debug_assert!(*slot == 0 || *slot == value, "magic collision on square {}", square);
+            *slot = value;
+            subset = subset.wrapping_sub(mask) & mask;
+            if subset == 0 {
+                break;
+            }
+        }
+
+        entries[square as usize] = entry;
+    }
+}
+
+/// Fill the tables for one indexing scheme. Both schemes address a square's
+/// slice with `relevant bits` bits, so the layout is the same either way.
+fn build_tables_with(index: fn(&Magic, Bitboard) -> usize) -> SliderTables {
+    let mut tables = SliderTables {
+        rook: [Magic::default(); 64],
+        bishop: [Magic::default(); 64],
+        attacks: Vec::with_capacity(102_400 + 5_248),
+    };
+
+    fill_slider(&mut tables.rook, &mut tables.attacks, &ROOK_MAGICS, rook_attacks_slow, index);
+    fill_slider(&mut tables.bishop, &mut tables.attacks, &BISHOP_MAGICS, bishop_attacks_slow, index);
+
+    tables
+}
+
+fn build_tables() -> SliderTables {
+    build_tables_with(Magic::index)
+}
+
+#[inline(always)]
+fn tables() -> &'static SliderTables {
+    TABLES.get_or_init(build_tables)
+}
+
+#[inline]
+pub fn rook_attacks(square: u8, occupied: Bitboard) -> Bitboard {
+    let t = tables();
+    t.attacks[t.rook[square as usize].index(occupied)]
+}
+
+#[inline]
+pub fn bishop_attacks(square: u8, occupied: Bitboard) -> Bitboard {
+    let t = tables();
+    t.attacks[t.bishop[square as usize].index(occupied)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Random occupancies with a mix of densities
+    fn sample_occupancies() -> Vec<Bitboard> {
+        let mut state: u64 = 0x0123_4567_89AB_CDEF;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut samples = vec![0, !0];
+        for _ in 0..100 {
+            samples.push(next());
+            samples.push(next() & next());
+            samples.push(next() & next() & next());
+        }
+        samples
+    }
+
+    fn assert_matches_reference(
+        fast: fn(u8, Bitboard) -> Bitboard,
+        slow: fn(u8, Bitboard) -> Bitboard,
+    ) {
+        let samples = sample_occupancies();
+        for square in 0..64 {
+            for &occupied in &samples {
+                assert_eq!(
+                    fast(square, occupied),
+                    slow(square, occupied),
+                    "square {} occupancy {:#018x}",
+                    square,
+                    occupied
+                );
+            }
+        }
+    }
+
+    /// Check a table built with one backend's indexing against the ray walk
+    fn assert_backend_matches_reference(index: fn(&Magic, Bitboard) -> usize) {
+        let tables = build_tables_with(index);
+        let samples = sample_occupancies();
+
+        for square in 0..64u8 {
+            for &occupied in &samples {
+                let rook = tables.attacks[index(&tables.rook[square as usize], occupied)];
+                let bishop = tables.attacks[index(&tables.bishop[square as usize], occupied)];
+                assert_eq!(rook, rook_attacks_slow(square, occupied));
+                assert_eq!(bishop, bishop_attacks_slow(square, occupied));
+            }
+        }
+    }
+
+    #[test]
+    fn rook_matches_ray_walk() {
+        assert_matches_reference(rook_attacks, rook_attacks_slow);
+    }
+
+    #[test]
+    fn bishop_matches_ray_walk() {
+        assert_matches_reference(bishop_attacks, bishop_attacks_slow);
+    }
+
+    #[test]
+    fn magic_backend_matches_ray_walk() {
+        assert_backend_matches_reference(Magic::magic_index);
+    }
+
+    // Only compiled in with the feature and BMI2 enabled; see Cargo.toml for
+    // the command that runs it
+    #[test]
+    #[cfg(all(feature = "pext", target_arch = "x86_64", target_feature = "bmi2"))]
+    fn pext_backend_matches_ray_walk() {
+        assert_backend_matches_reference(Magic::pext_index);
+    }
+
+    #[test]
+    fn relevance_mask_sizes() {
+        assert_eq!(tables().rook[0].mask.count_ones(), 12);
+        assert_eq!(tables().rook[27].mask.count_ones(), 10);
+        assert_eq!(tables().bishop[0].mask.count_ones(), 6);
+        assert_eq!(tables().bishop[27].mask.count_ones(), 9);
+        assert_eq!(tables().attacks.len(), 102_400 + 5_248);
+    }
+}
@@ -1,8 +1,5 @@
 pub mod attacks;
+pub mod magic;
 pub mod perft;
+#[allow(clippy::module_inception)]
 pub mod movegen;
-
-/// Initialize all attack tables. Call this before using the move generator.
-pub fn init() {
-    attacks::init_attack_tables();
-}
\ No newline at end of file
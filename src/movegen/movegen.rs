@@ -1,6 +1,6 @@
 
 use crate::board::board::Board;
-use crate::board::r#move::{Move, MoveList};
+use crate::board::r#move::*;
 use crate::board::piece::{Color, Piece};
 use crate::board::bitboard::*;
 use crate::movegen::attacks::{
@@ -9,20 +9,23 @@ use crate::movegen::attacks::{
     queen_attacks,
     knight_attacks,
     king_attacks,
+    white_pawn_attacks,
+    black_pawn_attacks,
+    between,
+    line,
 };
 
 
 #[inline]
-fn pop_lsb(bb: &mut u64) -> u8 {
-    let sq = bb.trailing_zeros() as u8;
-    *bb &= *bb - 1;
-    sq
+fn capture_flag(enemy: Bitboard, to: u8) -> u16 {
+    if enemy & bb(to) != 0 { CAPTURE } else { QUIET }
 }
 
 pub fn gen_knights(board: &Board, moves: &mut MoveList) {
     let color = board.side_to_move;
     let knights = board.pieces[color.index()][Piece::Knight.index()];
     let own = board.occupied_by(color);
+    let enemy = board.occupied_by(color.opposite());
 
     let mut bb = knights;
     while bb != 0 {
@@ -31,7 +34,7 @@ pub fn gen_knights(board: &Board, moves: &mut MoveList) {
 
         while targets != 0 {
             let to = pop_lsb(&mut targets);
-            moves.push(Move { from, to, promotion: None });
+            moves.push(Move::new(from, to, capture_flag(enemy, to)));
         }
     }
 }
@@ -41,6 +44,7 @@ pub fn gen_bishops(board: &Board, moves: &mut MoveList) {
     let color = board.side_to_move;
     let bishops = board.pieces[color.index()][Piece::Bishop.index()];
     let own = board.occupied_by(color);
+    let enemy = board.occupied_by(color.opposite());
     let occ = board.occupied;
 
     let mut bb = bishops;
@@ -50,7 +54,7 @@ pub fn gen_bishops(board: &Board, moves: &mut MoveList) {
 
         while targets != 0 {
             let to = pop_lsb(&mut targets);
-            moves.push(Move { from, to, promotion: None });
+            moves.push(Move::new(from, to, capture_flag(enemy, to)));
         }
     }
 }
@@ -59,6 +63,7 @@ pub fn gen_rooks(board: &Board, moves: &mut MoveList) {
     let color = board.side_to_move;
     let rooks = board.pieces[color.index()][Piece::Rook.index()];
     let own = board.occupied_by(color);
+    let enemy = board.occupied_by(color.opposite());
     let occ = board.occupied;
 
     let mut bb = rooks;
@@ -68,7 +73,7 @@ pub fn gen_rooks(board: &Board, moves: &mut MoveList) {
 
         while targets != 0 {
             let to = pop_lsb(&mut targets);
-            moves.push(Move { from, to, promotion: None });
+            moves.push(Move::new(from, to, capture_flag(enemy, to)));
         }
     }
 }
@@ -77,6 +82,7 @@ pub fn gen_queens(board: &Board, moves: &mut MoveList) {
     let color = board.side_to_move;
     let queens = board.pieces[color.index()][Piece::Queen.index()];
     let own = board.occupied_by(color);
+    let enemy = board.occupied_by(color.opposite());
     let occ = board.occupied;
 
     let mut bb = queens;
@@ -86,7 +92,7 @@ pub fn gen_queens(board: &Board, moves: &mut MoveList) {
 
         while targets != 0 {
             let to = pop_lsb(&mut targets);
-            moves.push(Move { from, to, promotion: None });
+            moves.push(Move::new(from, to, capture_flag(enemy, to)));
         }
     }
 }
@@ -95,13 +101,14 @@ pub fn gen_king(board: &Board, moves: &mut MoveList) {
     let color = board.side_to_move;
     let king = board.pieces[color.index()][Piece::King.index()];
     let own = board.occupied_by(color);
+    let enemy = board.occupied_by(color.opposite());
 
     let from = king.trailing_zeros() as u8;
     let mut targets = king_attacks(from) & !own;
 
     while targets != 0 {
         let to = pop_lsb(&mut targets);
-        moves.push(Move { from, to, promotion: None });
+        moves.push(Move::new(from, to, capture_flag(enemy, to)));
     }
 
     // Generate castling moves
@@ -112,6 +119,8 @@ pub fn gen_castling(board: &Board, moves: &mut MoveList) {
     let color = board.side_to_move;
     let occ = board.occupied;
     let enemy = color.opposite();
+    // Rights alone do not prove the rook is still there
+    let rooks = board.pieces[color.index()][Piece::Rook.index()];
 
     // Can't castle if in check
     if board.in_check(color) {
@@ -121,44 +130,44 @@ pub fn gen_castling(board: &Board, moves: &mut MoveList) {
     match color {
         Color::White => {
             // Kingside castling (e1g1)
-            if (board.castling_rights & CASTLE_WHITE_KING) != 0 {
+            if (board.castling_rights & CASTLE_WHITE_KING) != 0 && rooks & bb(7) != 0 {
                 // Check f1 and g1 are empty
                 if (occ & (bb(5) | bb(6))) == 0 {
                     // Check that f1 and g1 are not attacked
                     if !board.is_square_attacked(5, enemy) && !board.is_square_attacked(6, enemy) {
-                        moves.push(Move { from: 4, to: 6, promotion: None });
+                        moves.push(Move::new(4, 6, KING_CASTLE));
                     }
                 }
             }
             // Queenside castling (e1c1)
-            if (board.castling_rights & CASTLE_WHITE_QUEEN) != 0 {
+            if (board.castling_rights & CASTLE_WHITE_QUEEN) != 0 && rooks & bb(0) != 0 {
                 // Check b1, c1, d1 are empty
                 if (occ & (bb(1) | bb(2) | bb(3))) == 0 {
                     // Check that c1 and d1 are not attacked
                     if !board.is_square_attacked(2, enemy) && !board.is_square_attacked(3, enemy) {
-                        moves.push(Move { from: 4, to: 2, promotion: None });
+                        moves.push(Move::new(4, 2, QUEEN_CASTLE));
                     }
                 }
             }
         },
         Color::Black => {
             // Kingside castling (e8g8)
-            if (board.castling_rights & CASTLE_BLACK_KING) != 0 {
+            if (board.castling_rights & CASTLE_BLACK_KING) != 0 && rooks & bb(63) != 0 {
                 // Check f8 and g8 are empty
                 if (occ & (bb(61) | bb(62))) == 0 {
                     // Check that f8 and g8 are not attacked
                     if !board.is_square_attacked(61, enemy) && !board.is_square_attacked(62, enemy) {
-                        moves.push(Move { from: 60, to: 62, promotion: None });
+                        moves.push(Move::new(60, 62, KING_CASTLE));
                     }
                 }
             }
             // Queenside castling (e8c8)
-            if (board.castling_rights & CASTLE_BLACK_QUEEN) != 0 {
+            if (board.castling_rights & CASTLE_BLACK_QUEEN) != 0 && rooks & bb(56) != 0 {
                 // Check b8, c8, d8 are empty
                 if (occ & (bb(57) | bb(58) | bb(59))) == 0 {
                     // Check that c8 and d8 are not attacked
                     if !board.is_square_attacked(58, enemy) && !board.is_square_attacked(59, enemy) {
-                        moves.push(Move { from: 60, to: 58, promotion: None });
+                        moves.push(Move::new(60, 58, QUEEN_CASTLE));
                     }
                 }
             }
@@ -184,7 +193,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 let to = pop_lsb(&mut promo_pushes);
                 let from = to - 8;
                 for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                    moves.push(Move { from, to, promotion: Some(promo.index() as u8) });
+                    moves.push(Move::new_promotion(from, to, promo, false));
                 }
             }
             
@@ -192,11 +201,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             let mut targets = single_push & !RANK_8;
             while targets != 0 {
                 let to = pop_lsb(&mut targets);
-                moves.push(Move {
-                    from: to - 8,
-                    to,
-                    promotion: None,
-                });
+                moves.push(Move::new(to - 8, to, QUIET));
             }
 
             // Double pushes from rank 2
@@ -204,11 +209,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             let mut dbl = double_push;
             while dbl != 0 {
                 let to = pop_lsb(&mut dbl);
-                moves.push(Move {
-                    from: to - 16,
-                    to,
-                    promotion: None,
-                });
+                moves.push(Move::new(to - 16, to, DOUBLE_PUSH));
             }
 
             // Captures
@@ -221,7 +222,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 let to = pop_lsb(&mut promo_left);
                 let from = to - 7;
                 for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                    moves.push(Move { from, to, promotion: Some(promo.index() as u8) });
+                    moves.push(Move::new_promotion(from, to, promo, true));
                 }
             }
             
@@ -231,7 +232,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 let to = pop_lsb(&mut promo_right);
                 let from = to - 9;
                 for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                    moves.push(Move { from, to, promotion: Some(promo.index() as u8) });
+                    moves.push(Move::new_promotion(from, to, promo, true));
                 }
             }
 
@@ -240,7 +241,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             while left != 0 {
                 let to = pop_lsb(&mut left);
                 let from = to - 7;
-                moves.push(Move { from, to, promotion: None });
+                moves.push(Move::new(from, to, CAPTURE));
             }
             
             // Non-promotion captures - right
@@ -248,7 +249,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             while right != 0 {
                 let to = pop_lsb(&mut right);
                 let from = to - 9;
-                moves.push(Move { from, to, promotion: None });
+                moves.push(Move::new(from, to, CAPTURE));
             }
 
             // En passant captures
@@ -257,14 +258,14 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 if ep_sq % 8 > 0 { // ep square not on a-file
                     let from = ep_sq - 9; // one rank down, one file left
                     if pawns & bb(from) != 0 {
-                        moves.push(Move { from, to: ep_sq, promotion: None });
+                        moves.push(Move::new(from, ep_sq, EN_PASSANT));
                     }
                 }
                 // Check if a pawn can capture from the right (file+1)
                 if ep_sq % 8 < 7 { // ep square not on h-file
                     let from = ep_sq - 7; // one rank down, one file right
                     if pawns & bb(from) != 0 {
-                        moves.push(Move { from, to: ep_sq, promotion: None });
+                        moves.push(Move::new(from, ep_sq, EN_PASSANT));
                     }
                 }
             }
@@ -280,7 +281,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 let to = pop_lsb(&mut promo_pushes);
                 let from = to + 8;
                 for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                    moves.push(Move { from, to, promotion: Some(promo.index() as u8) });
+                    moves.push(Move::new_promotion(from, to, promo, false));
                 }
             }
             
@@ -288,11 +289,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             let mut targets = single_push & !RANK_1;
             while targets != 0 {
                 let to = pop_lsb(&mut targets);
-                moves.push(Move {
-                    from: to + 8,
-                    to,
-                    promotion: None,
-                });
+                moves.push(Move::new(to + 8, to, QUIET));
             }
 
             // Double pushes from rank 7
@@ -300,11 +297,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             let mut dbl = double_push;
             while dbl != 0 {
                 let to = pop_lsb(&mut dbl);
-                moves.push(Move {
-                    from: to + 16,
-                    to,
-                    promotion: None,
-                });
+                moves.push(Move::new(to + 16, to, DOUBLE_PUSH));
             }
 
             // Captures
@@ -317,7 +310,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 let to = pop_lsb(&mut promo_left);
                 let from = to + 9;
                 for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                    moves.push(Move { from, to, promotion: Some(promo.index() as u8) });
+                    moves.push(Move::new_promotion(from, to, promo, true));
                 }
             }
             
@@ -327,7 +320,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 let to = pop_lsb(&mut promo_right);
                 let from = to + 7;
                 for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                    moves.push(Move { from, to, promotion: Some(promo.index() as u8) });
+                    moves.push(Move::new_promotion(from, to, promo, true));
                 }
             }
 
@@ -336,7 +329,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             while left != 0 {
                 let to = pop_lsb(&mut left);
                 let from = to + 9;
-                moves.push(Move { from, to, promotion: None });
+                moves.push(Move::new(from, to, CAPTURE));
             }
             
             // Non-promotion captures - right
@@ -344,7 +337,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
             while right != 0 {
                 let to = pop_lsb(&mut right);
                 let from = to + 7;
-                moves.push(Move { from, to, promotion: None });
+                moves.push(Move::new(from, to, CAPTURE));
             }
 
             // En passant captures
@@ -353,14 +346,14 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
                 if ep_sq % 8 > 0 { // ep square not on a-file
                     let from = ep_sq + 7; // one rank up, one file left
                     if pawns & bb(from) != 0 {
-                        moves.push(Move { from, to: ep_sq, promotion: None });
+                        moves.push(Move::new(from, ep_sq, EN_PASSANT));
                     }
                 }
                 // Check if a pawn can capture from the right (file+1)
                 if ep_sq % 8 < 7 { // ep square not on h-file
                     let from = ep_sq + 9; // one rank up, one file right
                     if pawns & bb(from) != 0 {
-                        moves.push(Move { from, to: ep_sq, promotion: None });
+                        moves.push(Move::new(from, ep_sq, EN_PASSANT));
                     }
                 }
             }
@@ -369,7 +362,7 @@ pub fn gen_pawns(board: &Board, moves: &mut MoveList) {
 }
 
 pub fn generate_moves(board: &Board) -> MoveList {
-    let mut moves = Vec::with_capacity(64);
+    let mut moves = MoveList::new();
 
     gen_pawns(board, &mut moves);
     gen_knights(board, &mut moves);
@@ -381,18 +374,446 @@ pub fn generate_moves(board: &Board) -> MoveList {
     moves
 }
 
-pub fn generate_legal_moves(board: &mut Board) -> MoveList {
-    let color = board.side_to_move;
-    let pseudo_moves = generate_moves(board);
-    let mut legal_moves = Vec::with_capacity(pseudo_moves.len());
+/// Every square `color` attacks, with `occupied` as the blockers.
+fn attacked_squares(board: &Board, color: Color, occupied: Bitboard) -> Bitboard {
+    let pieces = &board.pieces[color.index()];
+    let pawns = pieces[Piece::Pawn.index()];
+    let mut attacked = match color {
+        Color::White => ((pawns << 7) & !FILE_H) | ((pawns << 9) & !FILE_A),
+        Color::Black => ((pawns >> 7) & !FILE_A) | ((pawns >> 9) & !FILE_H),
+    };
+
+    let mut knights = pieces[Piece::Knight.index()];
+    while knights != 0 {
+        attacked |= knight_attacks(pop_lsb(&mut knights));
+    }
+
+    let queens = pieces[Piece::Queen.index()];
+    let mut diagonal = pieces[Piece::Bishop.index()] | queens;
+    while diagonal != 0 {
+        attacked |= bishop_attacks(pop_lsb(&mut diagonal), occupied);
+    }
+
+    let mut orthogonal = pieces[Piece::Rook.index()] | queens;
+    while orthogonal != 0 {
+        attacked |= rook_attacks(pop_lsb(&mut orthogonal), occupied);
+    }
+
+    attacked | king_attacks(pieces[Piece::King.index()].trailing_zeros() as u8)
+}
+
+/// Pieces of the side to move that shield their king from an enemy slider.
+fn pinned_pieces(board: &Board, king_sq: u8) -> Bitboard {
+    let us = board.side_to_move;
+    let them = &board.pieces[us.opposite().index()];
+    let own = board.occupied_by(us);
+    let enemy = board.occupied_by(us.opposite());
+
+    // Look through our own pieces to find sliders lined up with the king
+    let queens = them[Piece::Queen.index()];
+    let mut snipers = (rook_attacks(king_sq, enemy) & (them[Piece::Rook.index()] | queens))
+        | (bishop_attacks(king_sq, enemy) & (them[Piece::Bishop.index()] | queens));
+
+    let mut pinned = 0;
+    while snipers != 0 {
+        let sniper = pop_lsb(&mut snipers);
+        let blockers = between(king_sq, sniper) & board.occupied;
+        if blockers.count_ones() == 1 && blockers & own != 0 {
+            pinned |= blockers;
+        }
+    }
+    pinned
+}
+
+#[inline]
+fn push_targets(moves: &mut MoveList, from: u8, mut targets: Bitboard, enemy: Bitboard) {
+    while targets != 0 {
+        let to = pop_lsb(&mut targets);
+        moves.push(Move::new(from, to, capture_flag(enemy, to)));
+    }
+}
+
+#[inline]
+fn push_pawn_targets(moves: &mut MoveList, from: u8, mut targets: Bitboard, enemy: Bitboard) {
+    while targets != 0 {
+        let to = pop_lsb(&mut targets);
+        let capture = enemy & bb(to) != 0;
+        if bb(to) & (RANK_1 | RANK_8) != 0 {
+            for promo in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+                moves.push(Move::new_promotion(from, to, promo, capture));
+            }
+        } else if from.abs_diff(to) == 16 {
+            moves.push(Move::new(from, to, DOUBLE_PUSH));
+        } else {
+            moves.push(Move::new(from, to, capture_flag(enemy, to)));
+        }
+    }
+}
+
+fn gen_legal_pawns(
+    board: &Board,
+    moves: &mut MoveList,
+    king_sq: u8,
+    pinned: Bitboard,
+    check_mask: Bitboard,
+    kind: GenKind,
+    from_mask: Bitboard,
+) {
+    let us = board.side_to_move;
+    let occ = board.occupied;
+    let enemy = board.occupied_by(us.opposite());
+
+    let mut pawns = board.pieces[us.index()][Piece::Pawn.index()] & from_mask;
+    while pawns != 0 {
+        let from = pop_lsb(&mut pawns);
+        let from_bb = bb(from);
+
+        let (single, double, captures) = match us {
+            Color::White => {
+                let single = (from_bb << 8) & !occ;
+                let double = ((single & RANK_3) << 8) & !occ;
+                (single, double, white_pawn_attacks(from) & enemy)
+            }
+            Color::Black => {
+                let single = (from_bb >> 8) & !occ;
+                let double = ((single & RANK_6) >> 8) & !occ;
+                (single, double, black_pawn_attacks(from) & enemy)
+            }
+        };
+
+        let targets = match kind {
+            GenKind::All => single | double | captures,
+            GenKind::Captures => captures | (single & (RANK_1 | RANK_8)),
+            GenKind::Quiets => (single & !(RANK_1 | RANK_8)) | double,
+        };
+        let mut targets = targets & check_mask;
+        if pinned & from_bb != 0 {
+            targets &= line(king_sq, from);
+        }
+        push_pawn_targets(moves, from, targets, enemy);
+    }
+
+    if kind != GenKind::Quiets {
+        gen_legal_en_passant(board, moves, king_sq, from_mask);
+    }
+}
 
-    for mv in pseudo_moves {
-        let undo = board.make_move(mv);
-        if !board.in_check(color) {
-            legal_moves.push(mv);
+/// En passant removes two pieces from the capturing pawn's rank, so pins and
+/// check masks are not enough. Replay the occupancy change and look for any
+/// attacker left on the king instead.
+fn gen_legal_en_passant(board: &Board, moves: &mut MoveList, king_sq: u8, from_mask: Bitboard) {
+    let Some(ep_sq) = board.en_passant_square else {
+        return;
+    };
+
+    let us = board.side_to_move;
+    let (candidates, captured_sq) = match us {
+        Color::White => (black_pawn_attacks(ep_sq), ep_sq - 8),
+        Color::Black => (white_pawn_attacks(ep_sq), ep_sq + 8),
+    };
+    let enemy = board.occupied_by(us.opposite());
+
+    let mut attackers = candidates & board.pieces[us.index()][Piece::Pawn.index()] & from_mask;
+    while attackers != 0 {
+        let from = pop_lsb(&mut attackers);
+        let occ_after = (board.occupied ^ bb(from) ^ bb(captured_sq)) | bb(ep_sq);
+        let checkers = board.attackers_to(king_sq, occ_after) & enemy & !bb(captured_sq);
+        if checkers == 0 {
+            moves.push(Move::new(from, ep_sq, EN_PASSANT));
         }
-        board.unmake_move(mv, undo);
     }
+}
 
-    legal_moves
-}
\ No newline at end of file
+/// Which legal moves to generate.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum GenKind {
+    All,
+    /// Captures, en passant and every promotion
+    Captures,
+    /// Everything else, castling included
+    Quiets,
+}
+
+/// Generate only legal moves. Checkers, pins and the squares the enemy
+/// attacks are worked out up front so no move needs to be played to test it.
+#[inline]
+pub fn generate_legal_moves(board: &Board) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_legal_moves_into(board, &mut moves);
+    moves
+}
+
+/// The legal move named `uci`, for tests that set up their moves by hand.
+#[cfg(test)]
+pub(crate) fn find_legal(board: &Board, uci: &str) -> Move {
+    *generate_legal_moves(board).iter().find(|m| m.to_uci() == uci).unwrap()
+}
+
+/// Append the legal moves to `moves`.
+pub fn generate_legal_moves_into(board: &Board, moves: &mut MoveList) {
+    generate_legal(board, moves, GenKind::All, !0);
+}
+
+/// Legal captures and promotions only, for quiescence search.
+#[inline]
+pub fn generate_legal_captures(board: &Board) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_legal_captures_into(board, &mut moves);
+    moves
+}
+
+pub fn generate_legal_captures_into(board: &Board, moves: &mut MoveList) {
+    generate_legal(board, moves, GenKind::Captures, !0);
+}
+
+/// Legal moves that neither capture nor promote. Together with
+/// `generate_legal_captures` this covers every legal move exactly once.
+#[inline]
+pub fn generate_legal_quiets(board: &Board) -> MoveList {
+    let mut moves = MoveList::new();
+    generate_legal_quiets_into(board, &mut moves);
+    moves
+}
+
+pub fn generate_legal_quiets_into(board: &Board, moves: &mut MoveList) {
+    generate_legal(board, moves, GenKind::Quiets, !0);
+}
+
+/// Whether `mv`, flags included, is legal here. Used to vet moves that come
+/// from elsewhere, such as the hash table or killer slots.
+pub fn is_legal(board: &Board, mv: Move) -> bool {
+    let own = board.occupied_by(board.side_to_move);
+    if mv.is_null() || own & bb(mv.from()) == 0 {
+        return false;
+    }
+
+    let kind = if mv.is_capture() || mv.is_promotion() {
+        GenKind::Captures
+    } else {
+        GenKind::Quiets
+    };
+    let mut moves = MoveList::new();
+    generate_legal(board, &mut moves, kind, bb(mv.from()));
+    moves.contains(&mv)
+}
+
+/// Generate the legal moves of `kind` for pieces on `from_mask`.
+fn generate_legal(board: &Board, moves: &mut MoveList, kind: GenKind, from_mask: Bitboard) {
+    let us = board.side_to_move;
+    let them = us.opposite();
+    let own = board.occupied_by(us);
+    let enemy = board.occupied_by(them);
+    let occ = board.occupied;
+    let king_sq = board.pieces[us.index()][Piece::King.index()].trailing_zeros() as u8;
+
+    let kind_mask = match kind {
+        GenKind::All => !own,
+        GenKind::Captures => enemy,
+        GenKind::Quiets => !occ,
+    };
+
+    // The king itself must not block enemy rays, or it could step back along a check
+    let king_moves = from_mask & bb(king_sq) != 0;
+    if king_moves {
+        let danger = attacked_squares(board, them, occ ^ bb(king_sq));
+        push_targets(moves, king_sq, king_attacks(king_sq) & kind_mask & !danger, enemy);
+    }
+
+    let checkers = board.attackers_to(king_sq, occ) & enemy;
+    if checkers.count_ones() > 1 {
+        return; // double check, only the king can move
+    }
+
+    // Non-king moves must capture the checker or block its ray
+    let check_mask = if checkers != 0 {
+        checkers | between(king_sq, checkers.trailing_zeros() as u8)
+    } else {
+        if kind != GenKind::Captures && king_moves {
+            gen_castling(board, moves);
+        }
+        !0
+    };
+
+    let pinned = pinned_pieces(board, king_sq);
+    let target_mask = kind_mask & check_mask;
+
+    gen_legal_pawns(board, moves, king_sq, pinned, check_mask, kind, from_mask);
+
+    // A pinned knight can never stay on its pin line
+    let mut knights = board.pieces[us.index()][Piece::Knight.index()] & !pinned & from_mask;
+    while knights != 0 {
+        let from = pop_lsb(&mut knights);
+        push_targets(moves, from, knight_attacks(from) & target_mask, enemy);
+    }
+
+    let pieces = &board.pieces[us.index()];
+    let queens = pieces[Piece::Queen.index()];
+    for (mut sliders, attacks) in [
+        (pieces[Piece::Bishop.index()] | queens, bishop_attacks as fn(u8, Bitboard) -> Bitboard),
+        (pieces[Piece::Rook.index()] | queens, rook_attacks),
+    ] {
+        sliders &= from_mask;
+        while sliders != 0 {
+            let from = pop_lsb(&mut sliders);
+            let mut targets = attacks(from, occ) & target_mask;
+            if pinned & bb(from) != 0 {
+                targets &= line(king_sq, from);
+            }
+            push_targets(moves, from, targets, enemy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Reference: pseudo-legal moves filtered by playing them
+    fn filtered_pseudo_legal(board: &mut Board) -> HashSet<Move> {
+        let color = board.side_to_move;
+        let mut legal = HashSet::new();
+        for mv in generate_moves(board) {
+            let undo = board.make_move(mv);
+            if !board.in_check(color) {
+                legal.insert(mv);
+            }
+            board.unmake_move(mv, undo);
+        }
+        legal
+    }
+
+    fn legal_set(board: &Board) -> HashSet<Move> {
+        let moves = generate_legal_moves(board);
+        let set: HashSet<_> = moves.iter().copied().collect();
+        assert_eq!(set.len(), moves.len(), "duplicate moves in {}", board.to_fen());
+        set
+    }
+
+    fn assert_matches_reference(board: &mut Board, depth: u32) {
+        assert_eq!(legal_set(board), filtered_pseudo_legal(board), "{}", board.to_fen());
+
+        let captures: HashSet<Move> = generate_legal_captures(board).iter().copied().collect();
+        let expected: HashSet<Move> = legal_set(board)
+            .into_iter()
+            .filter(|m| m.is_capture() || m.is_promotion())
+            .collect();
+        assert_eq!(captures, expected, "captures in {}", board.to_fen());
+
+        let quiets: HashSet<Move> = generate_legal_quiets(board).iter().copied().collect();
+        assert!(quiets.is_disjoint(&captures));
+        assert_eq!(quiets.len() + captures.len(), legal_set(board).len());
+
+        for mv in legal_set(board) {
+            assert!(is_legal(board, mv), "{:?} in {}", mv, board.to_fen());
+        }
+
+        if depth == 0 {
+            return;
+        }
+        for mv in generate_legal_moves(board) {
+            let undo = board.make_move(mv);
+            assert_matches_reference(board, depth - 1);
+            board.unmake_move(mv, undo);
+        }
+    }
+
+    fn legal_uci(fen: &str) -> Vec<String> {
+        let board = Board::from_fen(fen).unwrap();
+        let mut moves: Vec<String> = generate_legal_moves(&board).iter().map(|m| m.to_uci()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn matches_filtered_pseudo_legal() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert_matches_reference(&mut board, 2);
+        }
+    }
+
+    #[test]
+    fn is_legal_rejects_foreign_moves() {
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let legal = generate_legal_moves(&board);
+
+        // Moves from other positions, or with the wrong flags
+        for mv in [
+            Move::new(12, 28, DOUBLE_PUSH), // e2e4, no pawn on e2
+            Move::new(36, 44, QUIET),       // the e5 knight cannot reach e6
+            Move::new(4, 6, QUIET),         // castling without the flag
+            Move::new(35, 44, QUIET),       // d5xe6 without the capture flag
+            Move::new(52, 60, QUIET),       // black piece
+            Move::NULL,
+        ] {
+            assert_eq!(is_legal(&board, mv), legal.contains(&mv), "{:?}", mv);
+            assert!(!is_legal(&board, mv), "{:?}", mv);
+        }
+        assert!(is_legal(&board, Move::new(4, 6, KING_CASTLE)));
+        assert!(is_legal(&board, Move::new(35, 44, CAPTURE)));
+    }
+
+    #[test]
+    fn castling_needs_the_rook_at_home() {
+        // Rights left over after the h1 rook is removed by hand
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        board.pieces[Color::White.index()][Piece::Rook.index()] &= !bb(7);
+        board.refresh();
+
+        let moves: Vec<String> = generate_legal_moves(&board).iter().map(|m| m.to_uci()).collect();
+        assert!(!moves.contains(&"e1g1".to_string()));
+        assert!(moves.contains(&"e1c1".to_string()));
+    }
+
+    #[test]
+    fn en_passant_discovered_check_on_rank() {
+        // exd3 would leave both pawns off the fourth rank, exposing the king to the queen
+        let moves = legal_uci("8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1");
+        assert!(!moves.contains(&"e4d3".to_string()));
+    }
+
+    #[test]
+    fn en_passant_captures_checking_pawn() {
+        // d4 gives check, taking it en passant is the only non-king answer
+        let moves = legal_uci("8/8/8/8/3Pp3/4k3/8/3K4 b - d3 0 1");
+        assert!(moves.contains(&"e4d3".to_string()));
+    }
+
+    #[test]
+    fn en_passant_along_pin_diagonal() {
+        // The e4 pawn is pinned on the a8-h1 diagonal, so it cannot take on d3
+        let moves = legal_uci("k7/8/8/8/3Pp3/8/6B1/7K b - d3 0 1");
+        assert!(!moves.contains(&"e4d3".to_string()));
+    }
+
+    #[test]
+    fn pinned_piece_moves_along_pin() {
+        // The e2 rook is pinned by the e8 rook and can only slide on the e-file
+        let moves = legal_uci("4r2k/8/8/8/8/8/4R3/4K3 w - - 0 1");
+        let rook_moves: Vec<&String> = moves.iter().filter(|m| m.starts_with("e2")).collect();
+        assert_eq!(rook_moves.len(), 6);
+        assert!(rook_moves.iter().all(|m| m.as_bytes()[2] == b'e'));
+    }
+
+    #[test]
+    fn double_check_allows_only_king_moves() {
+        let moves = legal_uci("4k3/8/8/8/1b6/8/3P4/r3K2R w K - 0 1");
+        assert!(moves.iter().all(|m| m.starts_with("e1")));
+        assert!(!moves.contains(&"e1g1".to_string()));
+    }
+
+    #[test]
+    fn king_cannot_retreat_along_check_ray() {
+        let moves = legal_uci("4k3/8/8/8/8/8/8/r3K3 w - - 0 1");
+        assert!(!moves.contains(&"e1f1".to_string()));
+        assert!(moves.contains(&"e1e2".to_string()));
+    }
+}
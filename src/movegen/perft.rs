@@ -1,16 +1,21 @@
 use crate::board::board::Board;
 use crate::movegen::movegen::generate_legal_moves;
 
-/// Classic perft recursion
+/// Classic perft recursion. The generator only produces legal moves, so the
+/// last ply is counted from the move list instead of being played out.
 pub fn perft(board: &mut Board, depth: u32) -> u64 {
     if depth == 0 {
         return 1;
     }
 
-    let mut nodes = 0;
     let moves = generate_legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
 
-    for mv in moves {
+    let mut nodes = 0;
+    // By reference, so the list is not copied into an owning iterator
+    for &mv in moves.iter() {
         let undo = board.make_move(mv);
         nodes += perft(board, depth - 1);
         board.unmake_move(mv, undo);
@@ -37,7 +42,7 @@ pub fn perft_divide(board: &mut Board, depth: u32) -> u64 {
         };
         board.unmake_move(*mv, undo);
         
-        println!("{}{}: {}", square_name(mv.from), square_name(mv.to), count);
+        println!("{}: {}", mv.to_uci(), count);
         nodes += count;
     }
     
@@ -45,12 +50,6 @@ pub fn perft_divide(board: &mut Board, depth: u32) -> u64 {
     nodes
 }
 
-fn square_name(sq: u8) -> String {
-    let file = (sq % 8) as u8 + b'a';
-    let rank = (sq / 8) + 1;
-    format!("{}{}", file as char, rank)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -60,12 +59,12 @@ mod tests {
 
     #[test]
     fn no_duplicate_moves() {
-        let mut board = Board::startpos();
-        let moves = generate_legal_moves(&mut board);
+        let board = Board::startpos();
+        let moves = generate_legal_moves(&board);
         
         let mut seen = HashSet::new();
         for mv in &moves {
-            let key = (mv.from, mv.to);
+            let key = (mv.from(), mv.to());
             assert!(!seen.contains(&key), "Duplicate move: {:?}", mv);
             seen.insert(key);
         }
@@ -84,4 +83,24 @@ mod tests {
         assert_eq!(perft(&mut board, 4), 197_281);
         assert_eq!(perft(&mut board, 5), 4_865_609);
         }
+
+    #[test]
+    fn perft_tricky_positions() {
+        let cases: [(&str, &[u64]); 5] = [
+            // Kiwipete: castling through attacks, pins, en passant, promotions
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2_039, 97_862]),
+            // Rook endgame with en passant discovered checks along the rank
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2_812, 43_238]),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9_467]),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1_486, 62_379]),
+            ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2_079, 89_890]),
+        ];
+
+        for (fen, expected) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            for (depth, &nodes) in expected.iter().enumerate() {
+                assert_eq!(perft(&mut board, depth as u32 + 1), nodes, "{} depth {}", fen, depth + 1);
+            }
+        }
+    }
 }
@@ -1 +1,6 @@
-pub mod search;
\ No newline at end of file
+pub mod movepick;
+pub mod ordering;
+#[allow(clippy::module_inception)]
+pub mod search;
+pub mod time;
+pub mod tt;
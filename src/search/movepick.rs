@@ -0,0 +1,225 @@
+use crate::board::board::Board;
+use crate::board::r#move::{Move, MoveList, MAX_MOVES};
+use crate::eval::eval::piece_value;
+use crate::movegen::movegen::{
+    generate_legal_captures_into, generate_legal_moves_into, generate_legal_quiets_into, is_legal,
+};
+use crate::search::ordering::{captured_piece, mvv_lva, MoveOrdering};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    // Generation order, for measuring what the ordering is worth
+    GenerateAll,
+    All,
+    Done,
+}
+
+/// Hands out moves best first, generating each batch only when the previous
+/// one runs dry. Nodes that cut off on the hash move or a good capture never
+/// generate quiet moves at all.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Move,
+    killers: [Move; 2],
+    prev: Move,
+    moves: MoveList,
+    scores: [i32; MAX_MOVES],
+    index: usize,
+    bad_captures: MoveList,
+}
+
+/// A capture is put off until after the quiets when it trades a piece for a
+/// cheaper one on a square the opponent defends.
+fn is_bad_capture(board: &Board, mv: Move) -> bool {
+    if mv.is_promotion() {
+        return false;
+    }
+    let victim = captured_piece(board, mv).map_or(0, piece_value);
+    let attacker = board.piece_at(mv.from()).map_or(0, |(_, piece)| piece_value(piece));
+    attacker > victim && board.is_square_attacked(mv.to(), board.side_to_move.opposite())
+}
+
+impl MovePicker {
+    /// `prev` is the move that led to this node, for counter-move lookups.
+    pub fn new(ordering: &MoveOrdering, hash_move: Move, ply: usize, prev: Move) -> Self {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers: ordering.killers(ply),
+            prev,
+            moves: MoveList::new(),
+            scores: [0; MAX_MOVES],
+            index: 0,
+            bad_captures: MoveList::new(),
+        }
+    }
+
+    /// Every legal move in generation order, with no ordering at all.
+    pub fn unordered() -> Self {
+        MovePicker {
+            stage: Stage::GenerateAll,
+            hash_move: Move::NULL,
+            killers: [Move::NULL; 2],
+            prev: Move::NULL,
+            moves: MoveList::new(),
+            scores: [0; MAX_MOVES],
+            index: 0,
+            bad_captures: MoveList::new(),
+        }
+    }
+
+    pub fn next(&mut self, board: &Board, ordering: &MoveOrdering) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if is_legal(board, self.hash_move) {
+                        return Some(self.hash_move);
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    generate_legal_captures_into(board, &mut self.moves);
+                    for (score, &mv) in self.scores.iter_mut().zip(self.moves.iter()) {
+                        *score = mvv_lva(board, mv);
+                    }
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => match self.select_best() {
+                    Some(mv) if mv == self.hash_move => {}
+                    Some(mv) if is_bad_capture(board, mv) => self.bad_captures.push(mv),
+                    Some(mv) => return Some(mv),
+                    None => {
+                        self.stage = Stage::Killers;
+                        self.index = 0;
+                    }
+                },
+                Stage::Killers => {
+                    let Some(&killer) = self.killers.get(self.index) else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    self.index += 1;
+                    let quiet = !killer.is_capture() && !killer.is_promotion();
+                    if quiet && killer != self.hash_move && is_legal(board, killer) {
+                        return Some(killer);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    self.moves.clear();
+                    self.index = 0;
+                    generate_legal_quiets_into(board, &mut self.moves);
+                    let color = board.side_to_move;
+                    for (score, &mv) in self.scores.iter_mut().zip(self.moves.iter()) {
+                        *score = ordering.quiet_score(color, mv, self.prev);
+                    }
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.select_best() {
+                    Some(mv) if mv == self.hash_move || self.killers.contains(&mv) => {}
+                    Some(mv) => return Some(mv),
+                    None => {
+                        self.stage = Stage::BadCaptures;
+                        self.index = 0;
+                    }
+                },
+                Stage::BadCaptures => {
+                    // Already in MVV-LVA order
+                    let mv = self.bad_captures.get(self.index).copied();
+                    self.index += 1;
+                    if mv.is_none() {
+                        self.stage = Stage::Done;
+                    }
+                    return mv;
+                }
+                Stage::GenerateAll => {
+                    generate_legal_moves_into(board, &mut self.moves);
+                    self.stage = Stage::All;
+                }
+                Stage::All => {
+                    let mv = self.moves.get(self.index).copied();
+                    self.index += 1;
+                    return mv;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Swap the best remaining move to the front of the unpicked range.
+    fn select_best(&mut self) -> Option<Move> {
+        let remaining = self.moves.len().checked_sub(self.index).filter(|&n| n > 0)?;
+        let mut best = self.index;
+        for i in self.index + 1..self.index + remaining {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        self.index += 1;
+        Some(self.moves[self.index - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::piece::Color;
+    use crate::movegen::movegen::{find_legal, generate_legal_moves};
+    use std::collections::HashSet;
+
+    fn drain(board: &Board, ordering: &MoveOrdering, hash: Move, ply: usize) -> Vec<Move> {
+        let mut picker = MovePicker::new(ordering, hash, ply, Move::NULL);
+        let mut moves = Vec::new();
+        while let Some(mv) = picker.next(board, ordering) {
+            moves.push(mv);
+        }
+        moves
+    }
+
+    #[test]
+    fn yields_every_legal_move_once() {
+        let ordering = MoveOrdering::new();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/8/8/3Pp3/4k3/8/3K4 b - d3 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let legal: HashSet<Move> = generate_legal_moves(&board).iter().copied().collect();
+            let hash = *legal.iter().next().unwrap();
+
+            for hash in [Move::NULL, hash, Move::new(0, 63, 0)] {
+                let picked = drain(&board, &ordering, hash, 0);
+                let unique: HashSet<Move> = picked.iter().copied().collect();
+                assert_eq!(picked.len(), unique.len(), "{}", fen);
+                assert_eq!(unique, legal, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn stages_come_in_order() {
+        // The d5 queen can be taken by the e4 pawn or the c3 knight. Nxb5
+        // and Rxf7 give up a piece for a defended pawn.
+        let board = Board::from_fen("4k3/5p2/8/1p1q4/4P3/2N5/5R2/4K3 w - - 0 1").unwrap();
+        let mut ordering = MoveOrdering::new();
+
+        let hash = find_legal(&board, "e1f1");
+        let killer = find_legal(&board, "c3a4");
+        let quiet = find_legal(&board, "c3e2");
+        ordering.update_quiet(Color::White, killer, &[], 3, 2, Move::NULL);
+        ordering.update_quiet(Color::White, quiet, &[], 5, 4, Move::NULL);
+
+        let picked: Vec<String> = drain(&board, &ordering, hash, 3).iter().map(|m| m.to_uci()).collect();
+        assert_eq!(picked[..5], ["e1f1", "e4d5", "c3d5", "c3a4", "c3e2"]);
+        assert_eq!(picked[picked.len() - 2..], ["c3b5", "f2f7"]);
+    }
+}
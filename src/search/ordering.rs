@@ -0,0 +1,174 @@
+use crate::board::board::Board;
+use crate::board::piece::{Color, Piece};
+use crate::board::r#move::Move;
+use crate::eval::eval::piece_value;
+use crate::search::search::MAX_PLY;
+
+// Counter-moves rank above any history score.
+const COUNTER_MOVE: i32 = 100_000;
+
+/// History scores saturate towards this bound.
+const MAX_HISTORY: i32 = 16_384;
+
+/// The piece `mv` takes, if any.
+pub fn captured_piece(board: &Board, mv: Move) -> Option<Piece> {
+    if mv.is_en_passant() {
+        Some(Piece::Pawn)
+    } else if mv.is_capture() {
+        board.piece_at(mv.to()).map(|(_, piece)| piece)
+    } else {
+        None
+    }
+}
+
+/// Most valuable victim first, least valuable attacker breaking ties.
+/// Promotions count the gain of the new piece.
+pub fn mvv_lva(board: &Board, mv: Move) -> i32 {
+    let victim = captured_piece(board, mv).map_or(0, piece_value);
+    let attacker = board.piece_at(mv.from()).map_or(0, |(_, piece)| piece_value(piece));
+    let promotion = mv.promotion().map_or(0, |piece| piece_value(piece) - piece_value(Piece::Pawn));
+    (victim + promotion) * 16 - attacker / 100
+}
+
+/// Heuristics learned during the search: killer moves per ply, a butterfly
+/// history table indexed by side, from and to, and the refutation last seen
+/// for each previous move.
+pub struct MoveOrdering {
+    killers: [[Move; 2]; MAX_PLY],
+    history: [[[i32; 64]; 64]; 2],
+    counter_moves: [[Move; 64]; 64],
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering::new()
+    }
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        MoveOrdering {
+            killers: [[Move::NULL; 2]; MAX_PLY],
+            history: [[[0; 64]; 64]; 2],
+            counter_moves: [[Move::NULL; 64]; 64],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = MoveOrdering::new();
+    }
+
+    /// Keep what was learned but let the next search outweigh it. Killers
+    /// belong to the old tree and are dropped.
+    pub fn new_search(&mut self) {
+        self.killers = [[Move::NULL; 2]; MAX_PLY];
+        for entry in self.history.iter_mut().flatten().flatten() {
+            *entry /= 2;
+        }
+    }
+
+    pub fn killers(&self, ply: usize) -> [Move; 2] {
+        self.killers[ply]
+    }
+
+    pub fn history(&self, color: Color, mv: Move) -> i32 {
+        self.history[color.index()][mv.from() as usize][mv.to() as usize]
+    }
+
+    pub fn counter_move(&self, prev: Move) -> Move {
+        if prev.is_null() {
+            Move::NULL
+        } else {
+            self.counter_moves[prev.from() as usize][prev.to() as usize]
+        }
+    }
+
+    /// Rank a quiet move: the counter-move to `prev` first, then history.
+    pub fn quiet_score(&self, color: Color, mv: Move, prev: Move) -> i32 {
+        if mv == self.counter_move(prev) {
+            COUNTER_MOVE
+        } else {
+            self.history(color, mv)
+        }
+    }
+
+    /// Reward the quiet move that caused a beta cutoff and penalise the
+    /// quiets tried before it.
+    pub fn update_quiet(
+        &mut self,
+        color: Color,
+        mv: Move,
+        tried: &[Move],
+        ply: usize,
+        depth: u32,
+        prev: Move,
+    ) {
+        if self.killers[ply][0] != mv {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = mv;
+        }
+
+        if !prev.is_null() {
+            self.counter_moves[prev.from() as usize][prev.to() as usize] = mv;
+        }
+
+        let bonus = (depth * depth).min(400) as i32;
+        self.add_history(color, mv, bonus);
+        for &quiet in tried {
+            self.add_history(color, quiet, -bonus);
+        }
+    }
+
+    // Scaling the bonus by the distance left to the bound keeps entries
+    // within +-MAX_HISTORY however often they are hit
+    fn add_history(&mut self, color: Color, mv: Move, bonus: i32) {
+        let entry = &mut self.history[color.index()][mv.from() as usize][mv.to() as usize];
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::movegen::find_legal;
+
+    #[test]
+    fn mvv_lva_prefers_big_victims_and_small_attackers() {
+        // The d5 queen can be taken by the e4 pawn or the c3 knight; the b5
+        // pawn only by the knight
+        let board = Board::from_fen("4k3/8/8/1p1q4/4P3/2N5/8/4K3 w - - 0 1").unwrap();
+        let pxq = mvv_lva(&board, find_legal(&board, "e4d5"));
+        let nxq = mvv_lva(&board, find_legal(&board, "c3d5"));
+        let nxp = mvv_lva(&board, find_legal(&board, "c3b5"));
+        assert!(pxq > nxq && nxq > nxp);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let board = Board::startpos();
+        let mut ordering = MoveOrdering::new();
+        let mv = find_legal(&board, "g1f3");
+        let other = find_legal(&board, "b1c3");
+
+        for _ in 0..10_000 {
+            ordering.update_quiet(Color::White, mv, &[other], 1, 20, Move::NULL);
+        }
+        assert!(ordering.history(Color::White, mv) <= MAX_HISTORY);
+        assert!(ordering.history(Color::White, other) >= -MAX_HISTORY);
+        assert!(ordering.history(Color::White, other) < 0);
+    }
+
+    #[test]
+    fn counter_move_follows_previous_move() {
+        let mut board = Board::startpos();
+        let mut ordering = MoveOrdering::new();
+
+        let prev = find_legal(&board, "e2e4");
+        board.make_move(prev);
+        let reply = find_legal(&board, "b8c6");
+        ordering.update_quiet(Color::Black, reply, &[], 1, 3, prev);
+
+        assert_eq!(ordering.counter_move(prev), reply);
+        assert_eq!(ordering.quiet_score(Color::Black, reply, prev), COUNTER_MOVE);
+    }
+}
@@ -1,57 +1,773 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use crate::board::board::Board;
-use crate::board::r#move::Move;
-use crate::movegen::movegen::generate_legal_moves;
-use crate::eval::eval::evaluate;
+use crate::board::piece::Color;
+use crate::board::r#move::{Move, MoveList};
+use crate::movegen::movegen::{generate_legal_captures, generate_legal_moves};
+use crate::eval::eval::{evaluate, piece_value};
+use crate::eval::pawns::PawnTable;
+use crate::search::movepick::MovePicker;
+use crate::search::ordering::{captured_piece, mvv_lva, MoveOrdering};
+use crate::search::time::{SearchLimits, TimeManager, DEFAULT_MOVE_OVERHEAD};
+use crate::search::tt::{Bound, TranspositionTable, DEFAULT_HASH_MB};
 
-const INF: i32 = 1_000_000;
+pub const INF: i32 = 1_000_000;
 
-pub fn search(board: &mut Board, depth: u32) -> (i32, Option<Move>) {
-    let mut best_score = -INF;
-    let mut best_move = None;
+/// Mating at the root would score `MATE`; a mate in `n` plies scores `MATE - n`.
+pub const MATE: i32 = 900_000;
 
-    for mv in generate_legal_moves(board) {
-        let undo = board.make_move(mv);
-        let score = -negamax(board, depth - 1, -INF, INF);
-        board.unmake_move(mv, undo);
+/// Deepest ply the search tracks a principal variation for.
+pub const MAX_PLY: usize = 128;
 
-        if score > best_score {
-            best_score = score;
-            best_move = Some(mv);
-        }
+/// Slack for delta pruning: a capture is skipped when even winning the piece
+/// plus this margin leaves the score below alpha.
+const DELTA_MARGIN: i32 = 200;
+
+/// Root moves are only reported once the search has run this long, so short
+/// searches do not flood the GUI.
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1);
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+/// Progress after a completed iteration, and the final result of a search.
+#[derive(Clone, Debug, Default)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub seldepth: u32,
+    pub score: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    pub hashfull: usize, // per mille
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<Move> {
+        self.pv.first().copied()
+    }
+}
+
+/// Receives progress from a running search. Any `FnMut(&SearchInfo)` is a
+/// reporter that ignores root moves.
+pub trait SearchReporter {
+    /// Called after every completed iteration.
+    fn iteration(&mut self, info: &SearchInfo);
+
+    /// Called before each root move is searched, once the search has run
+    /// for a while. `number` counts from 1.
+    fn root_move(&mut self, _depth: u32, _mv: Move, _number: usize) {}
+}
+
+impl<F: FnMut(&SearchInfo)> SearchReporter for F {
+    fn iteration(&mut self, info: &SearchInfo) {
+        self(info)
     }
+}
 
-    (best_score, best_move)
+/// Flags another thread uses to steer a running search.
+#[derive(Debug, Default)]
+pub struct SearchControl {
+    stop: AtomicBool,
+    pondering: AtomicBool,
 }
 
-fn negamax(board: &mut Board, depth: u32, mut alpha: i32, beta: i32) -> i32 {
-    if depth == 0 {
-        return evaluate(board);
+impl SearchControl {
+    /// Prepare for a new search. Must be called before starting one, since
+    /// a stop request stays set until then.
+    pub fn reset(&self, ponder: bool) {
+        self.stop.store(false, Ordering::SeqCst);
+        self.pondering.store(ponder, Ordering::SeqCst);
+    }
+
+    /// Finish the current search as soon as it has a move, pondering or not.
+    pub fn stop(&self) {
+        self.pondering.store(false, Ordering::SeqCst);
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// The opponent played the expected move: keep searching, but on the clock.
+    pub fn ponderhit(&self) {
+        self.pondering.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Searcher {
+    tt: TranspositionTable,
+    pawns: PawnTable,
+    ordering: MoveOrdering,
+    nodes: u64,
+    root_best: Move,
+    // Length of the board's history at the root; earlier entries are game moves
+    root_history: usize,
+    // Only turned off to measure what move ordering saves
+    order_moves: bool,
+    // Move played to reach each ply, for counter-move lookups
+    move_stack: [Move; MAX_PLY],
+    control: Arc<SearchControl>,
+    time: TimeManager,
+    node_limit: Option<u64>,
+    move_overhead: Duration,
+    limits_active: bool,
+    pondering: bool,
+    stopped: bool,
+    start: Instant,
+    // Deepest ply reached in the current iteration
+    seldepth: usize,
+    // Triangular PV table: row `ply` holds the best line found from that ply
+    pv: [[Move; MAX_PLY]; MAX_PLY],
+    pv_len: [usize; MAX_PLY],
+}
+
+impl Default for Searcher {
+    fn default() -> Self {
+        Searcher::new()
+    }
+}
+
+impl Searcher {
+    pub fn new() -> Self {
+        Searcher {
+            tt: TranspositionTable::new(DEFAULT_HASH_MB),
+            pawns: PawnTable::new(),
+            ordering: MoveOrdering::new(),
+            nodes: 0,
+            root_best: Move::NULL,
+            root_history: 0,
+            order_moves: true,
+            move_stack: [Move::NULL; MAX_PLY],
+            control: Arc::new(SearchControl::default()),
+            time: TimeManager::new(&SearchLimits::default(), Color::White, Duration::ZERO, Instant::now()),
+            node_limit: None,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            limits_active: false,
+            pondering: false,
+            stopped: false,
+            start: Instant::now(),
+            seldepth: 0,
+            pv: [[Move::NULL; MAX_PLY]; MAX_PLY],
+            pv_len: [0; MAX_PLY],
+        }
+    }
+
+    pub fn tt(&self) -> &TranspositionTable {
+        &self.tt
+    }
+
+    pub fn tt_mut(&mut self) -> &mut TranspositionTable {
+        &mut self.tt
+    }
+
+    /// Forget everything learned from earlier games.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.pawns.clear();
+        self.ordering.clear();
+    }
+
+    /// Shared handle for stopping the search from another thread.
+    pub fn control(&self) -> Arc<SearchControl> {
+        Arc::clone(&self.control)
+    }
+
+    /// Time held back from every clock-based allocation.
+    pub fn set_move_overhead(&mut self, overhead: Duration) {
+        self.move_overhead = overhead;
     }
 
-    let moves = generate_legal_moves(board);
+    /// Search depth 1, 2, ... up to `max_depth`, calling `on_iteration` after
+    /// each completed depth. Returns the last completed iteration.
+    pub fn iterative_deepening<F: FnMut(&SearchInfo)>(
+        &mut self,
+        board: &mut Board,
+        max_depth: u32,
+        on_iteration: F,
+    ) -> SearchInfo {
+        self.search_with_limits(board, &SearchLimits::depth(max_depth), on_iteration)
+    }
+
+    /// Iterative deepening until one of `limits` is reached or the search is
+    /// stopped through its `SearchControl`. The first iteration always
+    /// completes, so there is a move to play however little time is left.
+    /// While pondering the clock is ignored; it starts on ponderhit.
+    pub fn search_with_limits<R: SearchReporter>(
+        &mut self,
+        board: &mut Board,
+        limits: &SearchLimits,
+        mut reporter: R,
+    ) -> SearchInfo {
+        let start = Instant::now();
+        self.start = start;
+        self.time = TimeManager::new(limits, board.side_to_move, self.move_overhead, start);
+        self.nodes = 0;
+        self.root_best = Move::NULL;
+        self.root_history = board.history.len();
+        self.node_limit = limits.nodes;
+        self.limits_active = false;
+        self.pondering = self.control.is_pondering();
+        self.stopped = false;
+        self.tt.new_search();
+        self.ordering.new_search();
+
+        let mut info = SearchInfo::default();
+        if generate_legal_moves(board).is_empty() {
+            info.score = if board.in_check(board.side_to_move) { -MATE } else { 0 };
+            return info;
+        }
+
+        // A mate in n moves shows up by depth 2n - 1
+        let mate_depth = limits.mate.map_or(u32::MAX, |moves| (2 * moves).saturating_sub(1).max(1));
+        let max_depth = limits.depth.unwrap_or(u32::MAX).min(mate_depth).min(MAX_PLY as u32 - 1);
+        for depth in 1..=max_depth {
+            self.seldepth = 0;
+            let score = self.negamax(board, &mut reporter, depth, 0, -INF, INF);
+            if self.stopped {
+                break; // the interrupted iteration is discarded
+            }
+
+            info = SearchInfo {
+                depth,
+                seldepth: self.seldepth as u32,
+                score,
+                nodes: self.nodes,
+                time: start.elapsed(),
+                hashfull: self.tt.hashfull(),
+                pv: self.pv[0][..self.pv_len[0]].to_vec(),
+            };
+            self.root_best = info.best_move().unwrap_or(Move::NULL);
+            reporter.iteration(&info);
+
+            // Limits only apply from here on
+            self.limits_active = true;
+            self.poll_ponderhit();
+
+            let mate_found = limits
+                .mate
+                .is_some_and(|moves| is_mate_score(score) && score > 0 && MATE - score < 2 * moves as i32);
+            let out_of_nodes = limits.nodes.is_some_and(|nodes| self.nodes >= nodes);
+            let out_of_time = !self.pondering && self.time.soft_expired();
+            if mate_found || out_of_nodes || out_of_time || self.control.is_stopped() {
+                break;
+            }
+        }
+
+        info
+    }
+
+    /// Cheap enough to call at every node: the clock and the stop flag are
+    /// only read every 1024 nodes.
+    #[inline]
+    fn check_limits(&mut self) {
+        if !self.limits_active {
+            return;
+        }
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            self.stopped = true;
+        }
+        if self.nodes & 1023 == 0 {
+            self.poll_ponderhit();
+            if self.control.is_stopped() || (!self.pondering && self.time.hard_expired()) {
+                self.stopped = true;
+            }
+        }
+    }
+
+    // Our clock starts running when the pondered move is played
+    fn poll_ponderhit(&mut self) {
+        if self.pondering && !self.control.is_pondering() {
+            self.pondering = false;
+            self.time.restart();
+        }
+    }
+
+    fn negamax<R: SearchReporter>(
+        &mut self,
+        board: &mut Board,
+        reporter: &mut R,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.pv_len[ply] = 0;
+        self.seldepth = self.seldepth.max(ply);
+
+        self.check_limits();
+        if self.stopped {
+            return 0;
+        }
 
-    if moves.is_empty() {
-        return if board.in_check(board.side_to_move) {
-            -INF + depth as i32 // checkmate
+        // A single repetition of a position after the root already counts as
+        // a draw, since the side that repeated can force it again. Positions
+        // from the game itself only draw on the third occurrence, as is_draw
+        // checks.
+        if ply > 0 && (board.is_draw() || board.is_repetition_since(self.root_history + 1)) {
+            // A mate delivered on the hundredth halfmove still stands
+            let fifty_moves = board.halfmove_clock >= 100;
+            if fifty_moves && board.in_check(board.side_to_move) && generate_legal_moves(board).is_empty() {
+                return -MATE + ply as i32;
+            }
+            return 0;
+        }
+
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        // Cut off on bounds that settle this node. An exact score inside the
+        // window is searched anyway so the principal variation stays complete.
+        let tt_hit = self.tt.probe(board.hash, ply);
+        if let Some(hit) = tt_hit
+            && ply > 0
+            && hit.depth as u32 >= depth
+        {
+            let fails_high = hit.score >= beta && hit.bound != Bound::Upper;
+            let fails_low = hit.score <= alpha && hit.bound != Bound::Lower;
+            if fails_high || fails_low {
+                return hit.score;
+            }
+        }
+
+        // Try the previous iteration's best move first at the root, and the
+        // hash move everywhere else. The first iteration of a search has no
+        // previous best, but the root may still be in the table.
+        let hash_move = match tt_hit {
+            _ if ply == 0 && self.root_best != Move::NULL => self.root_best,
+            Some(hit) => hit.best_move,
+            None => Move::NULL,
+        };
+        let prev = if ply > 0 { self.move_stack[ply - 1] } else { Move::NULL };
+        let mut picker = if self.order_moves {
+            MovePicker::new(&self.ordering, hash_move, ply, prev)
         } else {
-            0 // stalemate
+            MovePicker::unordered()
         };
+
+        let original_alpha = alpha;
+        let mut best = -INF;
+        let mut best_move = Move::NULL;
+        let mut quiets_tried = MoveList::new();
+        let mut move_number = 0;
+
+        while let Some(mv) = picker.next(board, &self.ordering) {
+            move_number += 1;
+            if ply == 0 && self.start.elapsed() >= CURRMOVE_DELAY {
+                reporter.root_move(depth, mv, move_number);
+            }
+
+            let undo = board.make_move(mv);
+            self.nodes += 1;
+            self.move_stack[ply] = mv;
+            let score = -self.negamax(board, reporter, depth - 1, ply + 1, -beta, -alpha);
+            board.unmake_move(mv, undo);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = mv;
+            }
+
+            if score > alpha {
+                alpha = score;
+                self.update_pv(ply, mv);
+            }
+
+            let quiet = !mv.is_capture() && !mv.is_promotion();
+            if alpha >= beta {
+                if quiet {
+                    let color = board.side_to_move;
+                    self.ordering.update_quiet(color, mv, &quiets_tried, ply, depth, prev);
+                }
+                break; // alpha-beta cutoff
+            }
+            if quiet {
+                quiets_tried.push(mv);
+            }
+        }
+
+        if best_move.is_null() {
+            return if board.in_check(board.side_to_move) {
+                -MATE + ply as i32 // checkmate
+            } else {
+                0 // stalemate
+            };
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // A fail-low has no trustworthy best move
+        let stored_move = if bound == Bound::Upper { Move::NULL } else { best_move };
+        self.tt.store(board.hash, depth, best, bound, stored_move, ply);
+
+        best
     }
 
-    let mut best = -INF;
+    /// Search captures and promotions until the position is quiet, so the
+    /// static evaluation is never taken in the middle of an exchange. When in
+    /// check every evasion is searched instead, since standing pat is not an
+    /// option.
+    fn quiescence(&mut self, board: &mut Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv_len[ply] = 0;
+        self.seldepth = self.seldepth.max(ply);
 
-    for mv in moves {
-        let undo = board.make_move(mv);
-        let score = -negamax(board, depth - 1, -beta, -alpha);
-        board.unmake_move(mv, undo);
+        self.check_limits();
+        if self.stopped {
+            return 0;
+        }
+
+        if ply >= MAX_PLY - 1 {
+            return evaluate(board, &mut self.pawns);
+        }
+
+        let in_check = board.in_check(board.side_to_move);
+        let mut best = -INF;
+        let mut moves = if in_check {
+            let evasions = generate_legal_moves(board);
+            if evasions.is_empty() {
+                return -MATE + ply as i32;
+            }
+            evasions
+        } else {
+            // Stand pat: the side to move can usually do at least as well as
+            // the current evaluation by playing a quiet move
+            let stand_pat = evaluate(board, &mut self.pawns);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best = stand_pat;
+            generate_legal_captures(board)
+        };
 
-        best = best.max(score);
-        alpha = alpha.max(score);
+        moves.sort_by_score(|&mv| mvv_lva(board, mv));
 
-        if alpha >= beta {
-            break; // alpha-beta cutoff
+        for mv in moves {
+            if !in_check && !mv.is_promotion() {
+                let victim = captured_piece(board, mv).map_or(0, piece_value);
+                if best + victim + DELTA_MARGIN <= alpha {
+                    continue; // delta pruning
+                }
+            }
+
+            let undo = board.make_move(mv);
+            self.nodes += 1;
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move(mv, undo);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
         }
+
+        best
+    }
+
+    /// `mv` followed by the child's line becomes the PV at `ply`.
+    fn update_pv(&mut self, ply: usize, mv: Move) {
+        let child_len = self.pv_len[ply + 1];
+        let (head, tail) = self.pv.split_at_mut(ply + 1);
+        head[ply][0] = mv;
+        head[ply][1..=child_len].copy_from_slice(&tail[0][..child_len]);
+        self.pv_len[ply] = child_len + 1;
+    }
+}
+
+/// Fixed-depth search returning the score and best move.
+pub fn search(board: &mut Board, depth: u32) -> (i32, Option<Move>) {
+    let info = Searcher::new().iterative_deepening(board, depth, |_| {});
+    (info.score, info.best_move())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::fen::STARTPOS_FEN;
+    use crate::board::piece::Piece;
+    use crate::movegen::movegen::find_legal;
+
+    const QUEEN_MINUS_PAWNS: i32 = 900 - 2 * 100;
+
+    // Material scores move by up to this much with piece placement
+    const POSITIONAL_SLACK: i32 = 100;
+
+    #[test]
+    fn fifty_move_rule_scores_draw() {
+        // Any queen or king move reaches the hundredth halfmove without a capture
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 99 80").unwrap();
+        let (score, best) = search(&mut board, 2);
+
+        assert_eq!(score, 0);
+        assert!(best.is_some());
     }
 
-    best
+    #[test]
+    fn mate_on_the_hundredth_halfmove_stands() {
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80").unwrap();
+        let info = Searcher::new().iterative_deepening(&mut board, 3, |_| {});
+
+        assert_eq!(info.best_move().unwrap().to_uci(), "a1a8");
+        assert_eq!(info.score, MATE - 1);
+    }
+
+    #[test]
+    fn first_iteration_tries_the_root_hash_move() {
+        // Every move draws by the fifty-move rule, so the first one searched
+        // stays best
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 99 80").unwrap();
+        let hash_move = find_legal(&board, "a1a7");
+        let mut searcher = Searcher::new();
+        searcher.tt.store(board.hash, 0, 0, Bound::Upper, hash_move, 0);
+
+        let info = searcher.iterative_deepening(&mut board, 1, |_| {});
+        assert_eq!(info.best_move(), Some(hash_move));
+    }
+
+    // Play `game` from `fen`, then `tree` as if searched from the position
+    // after `game`, and score the final position
+    fn score_after(fen: &str, game: &[&str], tree: &[&str]) -> i32 {
+        let mut board = Board::from_fen(fen).unwrap();
+        for uci in game {
+            board.make_move(Move::from_uci(uci).unwrap());
+        }
+        let mut searcher = Searcher::new();
+        searcher.root_history = board.history.len();
+        for uci in tree {
+            board.make_move(Move::from_uci(uci).unwrap());
+        }
+        searcher.negamax(&mut board, &mut |_: &SearchInfo| {}, 3, tree.len(), -INF, INF)
+    }
+
+    #[test]
+    fn repetition_in_tree_scores_draw() {
+        // The search returns to the position after 1. Nf3
+        let score = score_after(STARTPOS_FEN, &[], &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"]);
+        assert_eq!(score, 0);
+    }
+
+    #[test]
+    fn game_positions_draw_on_the_third_occurrence() {
+        // White is a queen and a knight up, so only a draw scores 0
+        const FEN: &str = "4k3/8/8/8/8/8/8/QN2K3 w - - 0 1";
+
+        // Kd8 repeats a game position once: not yet a draw
+        let score = score_after(FEN, &["b1c3", "e8d8", "c3b1", "d8e8", "b1c3"], &["e8d8"]);
+        assert!(score > 0, "{}", score);
+
+        // Ke8 reaches the starting position for the third time
+        let game = ["b1c3", "e8d8", "c3b1", "d8e8", "b1c3", "e8d8", "c3b1"];
+        assert_eq!(score_after(FEN, &game, &["d8e8"]), 0);
+    }
+
+    #[test]
+    fn reports_every_iteration() {
+        let mut board = Board::startpos();
+        let mut depths = Vec::new();
+        let info = Searcher::new().iterative_deepening(&mut board, 4, |info| {
+            assert_eq!(info.pv.len(), info.depth as usize);
+            assert!(info.seldepth >= info.depth);
+            depths.push(info.depth);
+        });
+
+        assert_eq!(depths, vec![1, 2, 3, 4]);
+        assert_eq!(info.depth, 4);
+        assert!(info.nodes > 0);
+    }
+
+    #[test]
+    fn pv_is_playable() {
+        let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let info = Searcher::new().iterative_deepening(&mut board, 3, |_| {});
+
+        assert_eq!(info.pv.len(), 3);
+        for mv in info.pv {
+            assert!(generate_legal_moves(&board).contains(&mv), "{:?} in {}", mv, board.to_fen());
+            board.make_move(mv);
+        }
+    }
+
+    #[test]
+    fn finds_shortest_mate() {
+        // Ra8# is mate in one; slower mates must not be preferred at depth 3
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let info = Searcher::new().iterative_deepening(&mut board, 3, |_| {});
+
+        assert_eq!(info.best_move().unwrap().to_uci(), "a1a8");
+        assert_eq!(info.score, MATE - 1);
+        assert_eq!(info.pv.len(), 1);
+        assert!(is_mate_score(info.score));
+    }
+
+    #[test]
+    fn sees_recapture_past_the_horizon() {
+        // Qxd5 wins a pawn at depth 1, but exd5 takes the queen back
+        let mut board = Board::from_fen("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let info = Searcher::new().iterative_deepening(&mut board, 1, |_| {});
+
+        assert_ne!(info.best_move().unwrap().to_uci(), "d1d5");
+        assert!((info.score - QUEEN_MINUS_PAWNS).abs() < POSITIONAL_SLACK, "{}", info.score);
+    }
+
+    #[test]
+    fn quiescence_resolves_exchanges() {
+        // White to move can win the undefended rook on d8 with the d1 rook
+        let mut board = Board::from_fen("3r2k1/8/8/8/8/8/8/3R2K1 w - - 0 1").unwrap();
+        let score = Searcher::new().quiescence(&mut board, 0, -INF, INF);
+        assert!((score - piece_value(Piece::Rook)).abs() < POSITIONAL_SLACK, "{}", score);
+    }
+
+    #[test]
+    fn quiescence_detects_mate_when_in_check() {
+        // Black is mated; standing pat must not hide it
+        let mut board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(Searcher::new().quiescence(&mut board, 3, -INF, INF), -MATE + 3);
+    }
+
+    #[test]
+    fn table_carries_over_between_searches() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        let mut searcher = Searcher::new();
+
+        let first = searcher.iterative_deepening(&mut board, 3, |_| {});
+        let second = searcher.iterative_deepening(&mut board, 3, |_| {});
+        assert!(second.nodes < first.nodes, "{} >= {}", second.nodes, first.nodes);
+        assert_eq!(second.score, first.score);
+
+        searcher.clear();
+        let cleared = searcher.iterative_deepening(&mut board, 3, |_| {});
+        assert_eq!(cleared.nodes, first.nodes);
+    }
+
+    #[test]
+    fn ordering_cuts_node_counts() {
+        // Against the same search trying moves in generation order
+        for fen in [
+            "rnbqkb1r/pp2pppp/2p2n2/3p4/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 2 4",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ] {
+            let nodes = |order_moves| {
+                let mut searcher = Searcher::new();
+                searcher.order_moves = order_moves;
+                searcher.iterative_deepening(&mut Board::from_fen(fen).unwrap(), 3, |_| {}).nodes
+            };
+            let (ordered, unordered) = (nodes(true), nodes(false));
+            assert!(ordered * 3 < unordered, "{} ordered, {} unordered nodes for {}", ordered, unordered, fen);
+        }
+    }
+
+    #[test]
+    fn stops_at_node_limit() {
+        let mut board = Board::startpos();
+        let limits = SearchLimits { nodes: Some(5_000), ..Default::default() };
+        let info = Searcher::new().search_with_limits(&mut board, &limits, |_: &SearchInfo| {});
+
+        assert!(info.best_move().is_some());
+        assert!(info.nodes <= 5_000, "{}", info.nodes);
+    }
+
+    #[test]
+    fn stops_at_movetime() {
+        let mut board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let limits = SearchLimits { movetime: Some(Duration::from_millis(200)), ..Default::default() };
+        let start = Instant::now();
+        let info = Searcher::new().search_with_limits(&mut board, &limits, |_: &SearchInfo| {});
+
+        assert!(info.best_move().is_some());
+        assert!(start.elapsed() < Duration::from_secs(2), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn stops_once_mate_is_found() {
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let limits = SearchLimits { mate: Some(1), infinite: true, ..Default::default() };
+        let info = Searcher::new().search_with_limits(&mut board, &limits, |_: &SearchInfo| {});
+
+        assert_eq!(info.score, MATE - 1);
+        assert_eq!(info.depth, 1);
+    }
+
+    #[test]
+    fn mate_in_two_needs_three_plies() {
+        // Ra7 and Rb8#
+        let mut board = Board::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1").unwrap();
+        let limits = SearchLimits { mate: Some(2), ..Default::default() };
+        let info = Searcher::new().search_with_limits(&mut board, &limits, |_: &SearchInfo| {});
+
+        assert_eq!(info.score, MATE - 3);
+        assert_eq!(info.depth, 3);
+    }
+
+    #[test]
+    fn stop_flag_ends_search() {
+        let mut board = Board::startpos();
+        let mut searcher = Searcher::new();
+        let control = searcher.control();
+        control.stop();
+
+        // Only the first iteration runs once stopped
+        let info = searcher.search_with_limits(&mut board, &SearchLimits { infinite: true, ..Default::default() }, |_: &SearchInfo| {});
+        assert_eq!(info.depth, 1);
+        assert!(info.best_move().is_some());
+
+        control.reset(false);
+        let info = searcher.iterative_deepening(&mut board, 3, |_| {});
+        assert_eq!(info.depth, 3);
+    }
+
+    #[test]
+    fn pondering_ignores_the_clock_until_ponderhit() {
+        let mut board = Board::startpos();
+        let mut searcher = Searcher::new();
+        let control = searcher.control();
+        control.reset(true);
+
+        let limits = SearchLimits { movetime: Some(Duration::from_millis(1)), ..Default::default() };
+        let handle = std::thread::spawn(move || searcher.search_with_limits(&mut board, &limits, |_: &SearchInfo| {}));
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!handle.is_finished());
+
+        control.ponderhit();
+        let info = handle.join().unwrap();
+        assert!(info.best_move().is_some());
+    }
+
+    #[test]
+    fn no_moves_at_root() {
+        // Black is checkmated
+        let mut board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        let info = Searcher::new().iterative_deepening(&mut board, 3, |_| {});
+
+        assert_eq!(info.best_move(), None);
+        assert_eq!(info.score, -MATE);
+    }
 }
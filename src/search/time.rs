@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+
+use crate::board::piece::Color;
+
+/// Held back from every allocation for communication lag with the GUI.
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(10);
+
+/// Moves left to plan for when the GUI does not send `movestogo`.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+
+/// Never plan to spend more than this many times the soft limit on one move.
+const HARD_LIMIT_FACTOR: u32 = 4;
+
+/// Percentage of the clock one move may use at most, even with `movestogo 1`.
+/// The rest is a reserve for GUI latency beyond the move overhead.
+const MAX_CLOCK_PERCENT: u32 = 75;
+
+/// Everything a UCI `go` command can constrain. Unset fields do not limit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub mate: Option<u32>, // in moves, not plies
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        SearchLimits { depth: Some(depth), ..Default::default() }
+    }
+}
+
+/// Deadlines for one search. The soft deadline is checked between
+/// iterations, since a new depth is unlikely to finish after it; the hard
+/// deadline aborts the search wherever it is.
+#[derive(Copy, Clone, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, side: Color, overhead: Duration, start: Instant) -> Self {
+        if limits.infinite {
+            return TimeManager { start, soft: None, hard: None };
+        }
+
+        if let Some(movetime) = limits.movetime {
+            let budget = movetime.saturating_sub(overhead);
+            return TimeManager { start, soft: Some(budget), hard: Some(budget) };
+        }
+
+        let (time, inc) = match side {
+            Color::White => (limits.wtime, limits.winc),
+            Color::Black => (limits.btime, limits.binc),
+        };
+        let Some(time) = time else {
+            return TimeManager { start, soft: None, hard: None };
+        };
+        let inc = inc.unwrap_or(Duration::ZERO);
+
+        // Keep the overhead and a share of what is left in reserve
+        let available = time.saturating_sub(overhead);
+        let cap = available * MAX_CLOCK_PERCENT / 100;
+        let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let soft = (available / moves_to_go + inc * 3 / 4).min(cap);
+        let hard = (soft * HARD_LIMIT_FACTOR).min(available / 2).max(soft);
+
+        TimeManager { start, soft: Some(soft), hard: Some(hard) }
+    }
+
+    /// Count the deadlines from now on.
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Whether another iteration is worth starting.
+    pub fn soft_expired(&self) -> bool {
+        self.soft.is_some_and(|soft| self.elapsed() >= soft)
+    }
+
+    pub fn hard_expired(&self) -> bool {
+        self.hard.is_some_and(|hard| self.elapsed() >= hard)
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        self.soft
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn manager(limits: &SearchLimits, side: Color) -> TimeManager {
+        TimeManager::new(limits, side, DEFAULT_MOVE_OVERHEAD, Instant::now())
+    }
+
+    #[test]
+    fn unlimited_without_clock() {
+        for limits in [
+            SearchLimits::default(),
+            SearchLimits::depth(5),
+            SearchLimits { infinite: true, wtime: Some(ms(1000)), ..Default::default() },
+        ] {
+            let tm = manager(&limits, Color::White);
+            assert_eq!(tm.soft_limit(), None);
+            assert_eq!(tm.hard_limit(), None);
+            assert!(!tm.hard_expired());
+        }
+    }
+
+    #[test]
+    fn movetime_is_fixed() {
+        let limits = SearchLimits { movetime: Some(ms(500)), ..Default::default() };
+        let tm = manager(&limits, Color::Black);
+        assert_eq!(tm.soft_limit(), Some(ms(490)));
+        assert_eq!(tm.hard_limit(), Some(ms(490)));
+    }
+
+    #[test]
+    fn uses_own_clock() {
+        let limits = SearchLimits {
+            wtime: Some(ms(60_010)),
+            btime: Some(ms(3_000)),
+            winc: Some(ms(1_000)),
+            ..Default::default()
+        };
+
+        let white = manager(&limits, Color::White);
+        let black = manager(&limits, Color::Black);
+        assert_eq!(white.soft_limit(), Some(ms(60_000 / 30 + 750)));
+        assert!(black.soft_limit().unwrap() < ms(200));
+        assert!(white.hard_limit() > white.soft_limit());
+    }
+
+    #[test]
+    fn movestogo_spreads_remaining_time() {
+        let limits = SearchLimits { btime: Some(ms(10_010)), movestogo: Some(2), ..Default::default() };
+        let tm = manager(&limits, Color::Black);
+        assert_eq!(tm.soft_limit(), Some(ms(5_000)));
+        assert_eq!(tm.hard_limit(), Some(ms(5_000)));
+    }
+
+    #[test]
+    fn never_plans_past_the_flag() {
+        for time in [0, 5, 50, 500] {
+            let limits = SearchLimits {
+                wtime: Some(ms(time)),
+                winc: Some(ms(2_000)),
+                movestogo: Some(1),
+                ..Default::default()
+            };
+            let tm = manager(&limits, Color::White);
+            assert!(tm.hard_limit().unwrap() <= ms(time).saturating_sub(DEFAULT_MOVE_OVERHEAD));
+        }
+    }
+
+    #[test]
+    fn keeps_a_reserve_on_the_last_move_before_the_control() {
+        let limits = SearchLimits { wtime: Some(ms(60_010)), movestogo: Some(1), ..Default::default() };
+        let tm = manager(&limits, Color::White);
+        assert_eq!(tm.soft_limit(), Some(ms(45_000)));
+        assert_eq!(tm.hard_limit(), Some(ms(45_000)));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::board::r#move::Move;
+use crate::search::search::{MATE, MAX_PLY};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 65536;
+
+/// How a stored score relates to the true value of the position.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower, // failed high, true score >= stored
+    Upper, // failed low, true score <= stored
+}
+
+#[derive(Copy, Clone, Default)]
+struct Entry {
+    key: u32, // high half of the hash; the low bits pick the slot
+    score: i32,
+    best_move: Move,
+    depth: u8,
+    bound: Option<Bound>, // None marks an empty slot
+    age: u8,
+}
+
+/// What a probe hands back to the search. The score is already relative to
+/// the probing ply.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TTHit {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+pub struct TranspositionTable {
+    entries: Vec<Entry>,
+    mask: usize,
+    age: u8,
+}
+
+/// Mate scores are stored as distance from the node rather than the root,
+/// so they stay valid when the position is reached at another ply.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+impl TranspositionTable {
+    pub fn new(mb: usize) -> Self {
+        let mut tt = TranspositionTable { entries: Vec::new(), mask: 0, age: 0 };
+        tt.resize(mb);
+        tt
+    }
+
+    /// Reallocate to the largest power-of-two entry count that fits in `mb`
+    /// megabytes. Clears the table.
+    pub fn resize(&mut self, mb: usize) {
+        let bytes = mb.clamp(1, MAX_HASH_MB) * 1024 * 1024;
+        let count = bytes / std::mem::size_of::<Entry>();
+        let count = 1 << count.ilog2();
+
+        self.entries = vec![Entry::default(); count];
+        self.mask = count - 1;
+        self.age = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(Entry::default());
+        self.age = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Call once per search so entries from earlier searches can be told apart.
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    #[inline]
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & self.mask
+    }
+
+    pub fn probe(&self, hash: u64, ply: usize) -> Option<TTHit> {
+        let entry = &self.entries[self.index(hash)];
+        let bound = entry.bound?;
+        if entry.key != (hash >> 32) as u32 {
+            return None;
+        }
+
+        Some(TTHit {
+            best_move: entry.best_move,
+            score: score_from_tt(entry.score, ply),
+            depth: entry.depth,
+            bound,
+        })
+    }
+
+    /// Store a search result. A deeper entry from the current search survives
+    /// a shallower result for another position; anything else is replaced.
+    pub fn store(&mut self, hash: u64, depth: u32, score: i32, bound: Bound, best_move: Move, ply: usize) {
+        let key = (hash >> 32) as u32;
+        let depth = depth.min(u8::MAX as u32) as u8;
+        let age = self.age;
+        let index = self.index(hash);
+        let entry = &mut self.entries[index];
+
+        let same_position = entry.bound.is_some() && entry.key == key;
+        if !same_position && entry.bound.is_some() && entry.age == age && depth < entry.depth {
+            return;
+        }
+
+        // Keep the old move if this result did not produce one
+        let best_move = if best_move.is_null() && same_position {
+            entry.best_move
+        } else {
+            best_move
+        };
+
+        *entry = Entry {
+            key,
+            score: score_to_tt(score, ply),
+            best_move,
+            depth,
+            bound: Some(bound),
+            age,
+        };
+    }
+
+    /// Permille of sampled slots filled during the current search.
+    pub fn hashfull(&self) -> usize {
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        let used = sample.iter().filter(|e| e.bound.is_some() && e.age == self.age).count();
+        used * 1000 / sample.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::r#move::QUIET;
+
+    const HASH: u64 = 0x1234_5678_9ABC_DEF0;
+
+    #[test]
+    fn size_is_a_power_of_two() {
+        let tt = TranspositionTable::new(1);
+        assert!(tt.len().is_power_of_two());
+        assert!(tt.len() * std::mem::size_of::<Entry>() <= 1024 * 1024);
+
+        let tt = TranspositionTable::new(3);
+        assert_eq!(tt.len() * std::mem::size_of::<Entry>(), 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let mut tt = TranspositionTable::new(1);
+        let mv = Move::new(12, 28, QUIET);
+        tt.store(HASH, 5, 42, Bound::Lower, mv, 3);
+
+        let hit = tt.probe(HASH, 3).unwrap();
+        assert_eq!(hit, TTHit { best_move: mv, score: 42, depth: 5, bound: Bound::Lower });
+
+        // Same slot, different key
+        assert_eq!(tt.probe(HASH ^ (1 << 40), 3), None);
+    }
+
+    #[test]
+    fn mate_scores_are_ply_relative() {
+        let mut tt = TranspositionTable::new(1);
+
+        // Mate in 3 plies from a node at ply 4 is mate in 7 from the root
+        tt.store(HASH, 3, MATE - 7, Bound::Exact, Move::NULL, 4);
+        assert_eq!(tt.probe(HASH, 4).unwrap().score, MATE - 7);
+        assert_eq!(tt.probe(HASH, 10).unwrap().score, MATE - 13);
+
+        tt.store(HASH, 3, -MATE + 7, Bound::Exact, Move::NULL, 4);
+        assert_eq!(tt.probe(HASH, 2).unwrap().score, -MATE + 5);
+    }
+
+    #[test]
+    fn replacement_keeps_deeper_entries() {
+        let mut tt = TranspositionTable::new(1);
+        let other = HASH ^ (1 << 40);
+
+        tt.store(HASH, 8, 10, Bound::Exact, Move::NULL, 0);
+        tt.store(other, 2, 20, Bound::Exact, Move::NULL, 0);
+        assert_eq!(tt.probe(HASH, 0).unwrap().depth, 8);
+
+        // Entries from an earlier search are fair game
+        tt.new_search();
+        tt.store(other, 2, 20, Bound::Exact, Move::NULL, 0);
+        assert_eq!(tt.probe(HASH, 0), None);
+        assert_eq!(tt.probe(other, 0).unwrap().score, 20);
+    }
+
+    #[test]
+    fn keeps_move_when_none_given() {
+        let mut tt = TranspositionTable::new(1);
+        let mv = Move::new(6, 21, QUIET);
+        tt.store(HASH, 4, 0, Bound::Lower, mv, 0);
+        tt.store(HASH, 5, -30, Bound::Upper, Move::NULL, 0);
+        assert_eq!(tt.probe(HASH, 0).unwrap().best_move, mv);
+    }
+
+    #[test]
+    fn clear_and_hashfull() {
+        let mut tt = TranspositionTable::new(1);
+        assert_eq!(tt.hashfull(), 0);
+
+        for i in 0..500u64 {
+            tt.store(i, 1, 0, Bound::Exact, Move::NULL, 0);
+        }
+        assert_eq!(tt.hashfull(), 500);
+
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+
+        tt.clear();
+        assert_eq!(tt.probe(7, 0), None);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod options;
+#[allow(clippy::module_inception)]
 pub mod uci;
 
-pub use uci::uci_loop;
\ No newline at end of file
+pub use uci::uci_loop;
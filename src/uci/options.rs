@@ -0,0 +1,216 @@
+use std::fmt;
+
+use crate::search::time::DEFAULT_MOVE_OVERHEAD;
+use crate::search::tt::{DEFAULT_HASH_MB, MAX_HASH_MB};
+
+pub const HASH: &str = "Hash";
+pub const THREADS: &str = "Threads";
+pub const MULTI_PV: &str = "MultiPV";
+pub const CLEAR_HASH: &str = "Clear Hash";
+pub const MOVE_OVERHEAD: &str = "Move Overhead";
+pub const PONDER: &str = "Ponder";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OptionKind {
+    Spin { default: i64, min: i64, max: i64 },
+    Check { default: bool },
+    Combo { default: &'static str, vars: &'static [&'static str] },
+    String { default: &'static str },
+    Button,
+}
+
+/// An option as advertised in the reply to `uci`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UciOption {
+    pub name: &'static str,
+    pub kind: OptionKind,
+}
+
+/// Every option the engine understands. The search is single threaded and
+/// reports one line, so Threads and MultiPV only accept 1 for now.
+pub const OPTIONS: &[UciOption] = &[
+    UciOption {
+        name: HASH,
+        kind: OptionKind::Spin { default: DEFAULT_HASH_MB as i64, min: 1, max: MAX_HASH_MB as i64 },
+    },
+    UciOption { name: THREADS, kind: OptionKind::Spin { default: 1, min: 1, max: 1 } },
+    UciOption { name: MULTI_PV, kind: OptionKind::Spin { default: 1, min: 1, max: 1 } },
+    UciOption { name: CLEAR_HASH, kind: OptionKind::Button },
+    UciOption {
+        name: MOVE_OVERHEAD,
+        kind: OptionKind::Spin { default: DEFAULT_MOVE_OVERHEAD.as_millis() as i64, min: 0, max: 5000 },
+    },
+    UciOption { name: PONDER, kind: OptionKind::Check { default: false } },
+];
+
+/// A validated `setoption` value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    Text(String), // combo and string options
+    Button,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionError {
+    MissingName,
+    UnknownOption(String),
+    MissingValue(&'static str),
+    NotANumber { name: &'static str, value: String },
+    OutOfRange { name: &'static str, value: i64, min: i64, max: i64 },
+    NotABool { name: &'static str, value: String },
+    UnknownVar { name: &'static str, value: String },
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionError::MissingName => write!(f, "setoption needs a name"),
+            OptionError::UnknownOption(name) => write!(f, "unknown option '{}'", name),
+            OptionError::MissingValue(name) => write!(f, "option {} needs a value", name),
+            OptionError::NotANumber { name, value } => {
+                write!(f, "option {} expects a number, got '{}'", name, value)
+            }
+            OptionError::OutOfRange { name, value, min, max } => {
+                write!(f, "option {} must be between {} and {}, got {}", name, min, max, value)
+            }
+            OptionError::NotABool { name, value } => {
+                write!(f, "option {} expects true or false, got '{}'", name, value)
+            }
+            OptionError::UnknownVar { name, value } => write!(f, "option {} has no value '{}'", name, value),
+        }
+    }
+}
+
+impl std::error::Error for OptionError {}
+
+impl fmt::Display for UciOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match self.kind {
+            OptionKind::Spin { default, min, max } => write!(f, "spin default {} min {} max {}", default, min, max),
+            OptionKind::Check { default } => write!(f, "check default {}", default),
+            OptionKind::Combo { default, vars } => {
+                write!(f, "combo default {}", default)?;
+                for var in vars {
+                    write!(f, " var {}", var)?;
+                }
+                Ok(())
+            }
+            OptionKind::String { default } => write!(f, "string default {}", default),
+            OptionKind::Button => write!(f, "button"),
+        }
+    }
+}
+
+impl UciOption {
+    /// Option names are matched case-insensitively, as the protocol asks.
+    pub fn find(name: &str) -> Option<&'static UciOption> {
+        OPTIONS.iter().find(|option| option.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Check `value` against the option's type and range.
+    pub fn parse_value(&self, value: Option<&str>) -> Result<OptionValue, OptionError> {
+        let name = self.name;
+        let Some(value) = value else {
+            return match self.kind {
+                OptionKind::Button => Ok(OptionValue::Button),
+                _ => Err(OptionError::MissingValue(name)),
+            };
+        };
+
+        match self.kind {
+            OptionKind::Spin { min, max, .. } => {
+                let number = value
+                    .parse()
+                    .map_err(|_| OptionError::NotANumber { name, value: value.to_string() })?;
+                if !(min..=max).contains(&number) {
+                    return Err(OptionError::OutOfRange { name, value: number, min, max });
+                }
+                Ok(OptionValue::Spin(number))
+            }
+            OptionKind::Check { .. } => match value {
+                _ if value.eq_ignore_ascii_case("true") => Ok(OptionValue::Check(true)),
+                _ if value.eq_ignore_ascii_case("false") => Ok(OptionValue::Check(false)),
+                _ => Err(OptionError::NotABool { name, value: value.to_string() }),
+            },
+            OptionKind::Combo { vars, .. } => vars
+                .iter()
+                .find(|var| var.eq_ignore_ascii_case(value))
+                .map(|var| OptionValue::Text(var.to_string()))
+                .ok_or_else(|| OptionError::UnknownVar { name, value: value.to_string() }),
+            OptionKind::String { .. } => Ok(OptionValue::Text(value.to_string())),
+            OptionKind::Button => Ok(OptionValue::Button),
+        }
+    }
+}
+
+/// Parse the arguments of `setoption`: `name <id> [value <x>]`. Both the
+/// name and the value may contain spaces.
+pub fn parse_setoption(args: &str) -> Result<(&'static UciOption, OptionValue), OptionError> {
+    let mut tokens = args.split_whitespace();
+    if tokens.next() != Some("name") {
+        return Err(OptionError::MissingName);
+    }
+
+    let name: Vec<&str> = tokens.by_ref().take_while(|&token| token != "value").collect();
+    let value: Vec<&str> = tokens.collect();
+    if name.is_empty() {
+        return Err(OptionError::MissingName);
+    }
+
+    let name = name.join(" ");
+    let option = UciOption::find(&name).ok_or(OptionError::UnknownOption(name))?;
+    let value = value.join(" ");
+    let value = option.parse_value(Some(value.as_str()).filter(|value| !value.is_empty()))?;
+    Ok((option, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advertises_every_type() {
+        assert_eq!(UciOption::find(HASH).unwrap().to_string(), "option name Hash type spin default 16 min 1 max 65536");
+        assert_eq!(UciOption::find(PONDER).unwrap().to_string(), "option name Ponder type check default false");
+        assert_eq!(UciOption::find(CLEAR_HASH).unwrap().to_string(), "option name Clear Hash type button");
+
+        let combo = UciOption { name: "Style", kind: OptionKind::Combo { default: "Normal", vars: &["Solid", "Normal"] } };
+        assert_eq!(combo.to_string(), "option name Style type combo default Normal var Solid var Normal");
+        let string = UciOption { name: "Book", kind: OptionKind::String { default: "<empty>" } };
+        assert_eq!(string.to_string(), "option name Book type string default <empty>");
+    }
+
+    #[test]
+    fn parses_setoption() {
+        let parse = |args: &str| parse_setoption(args).map(|(option, value)| (option.name, value));
+        assert_eq!(parse("name Hash value 64"), Ok((HASH, OptionValue::Spin(64))));
+        assert_eq!(parse("name move overhead value 30"), Ok((MOVE_OVERHEAD, OptionValue::Spin(30))));
+        assert_eq!(parse("name Clear Hash"), Ok((CLEAR_HASH, OptionValue::Button)));
+        assert_eq!(parse("name Ponder value true"), Ok((PONDER, OptionValue::Check(true))));
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let error = |args: &str| parse_setoption(args).err();
+        assert_eq!(error("Hash value 64"), Some(OptionError::MissingName));
+        assert_eq!(error("name value 1"), Some(OptionError::MissingName));
+        assert_eq!(error("name Contempt value 10"), Some(OptionError::UnknownOption("Contempt".to_string())));
+        assert_eq!(error("name Hash"), Some(OptionError::MissingValue(HASH)));
+        assert_eq!(error("name Hash value big"), Some(OptionError::NotANumber { name: HASH, value: "big".to_string() }));
+        assert_eq!(
+            error("name Threads value 4"),
+            Some(OptionError::OutOfRange { name: THREADS, value: 4, min: 1, max: 1 })
+        );
+        assert_eq!(error("name Ponder value yes"), Some(OptionError::NotABool { name: PONDER, value: "yes".to_string() }));
+
+        let combo = UciOption { name: "Style", kind: OptionKind::Combo { default: "Normal", vars: &["Solid", "Normal"] } };
+        assert_eq!(combo.parse_value(Some("solid")), Ok(OptionValue::Text("Solid".to_string())));
+        assert_eq!(
+            combo.parse_value(Some("Risky")),
+            Err(OptionError::UnknownVar { name: "Style", value: "Risky".to_string() })
+        );
+    }
+}
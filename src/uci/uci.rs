@@ -1,59 +1,391 @@
 use crate::board::board::Board;
+use crate::board::fen::FenError;
 use crate::board::r#move::Move;
-use crate::search::search::search;
+use crate::movegen::movegen::generate_legal_moves;
+use crate::search::search::{is_mate_score, SearchControl, SearchInfo, SearchReporter, Searcher, MATE};
+use crate::search::time::SearchLimits;
+use crate::uci::options::{self, parse_setoption, OptionValue, UciOption, OPTIONS};
+use std::fmt;
 use std::io::{self, Write};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The search recurses up to MAX_PLY deep with a move list in every frame,
+/// more than the default thread stack is guaranteed to hold.
+const SEARCH_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Holds the searcher while it is idle and the worker thread while it is
+/// searching. The worker hands the searcher back when it finishes, so the
+/// hash table and move ordering carry over to the next search.
+struct Engine {
+    board: Board,
+    searcher: Option<Searcher>,
+    worker: Option<JoinHandle<Searcher>>,
+    control: Arc<SearchControl>,
+}
+
+impl Engine {
+    fn new() -> Self {
+        let searcher = Searcher::new();
+        let control = searcher.control();
+        Engine { board: Board::startpos(), searcher: Some(searcher), worker: None, control }
+    }
+
+    /// Stop a running search and wait for its `bestmove`.
+    fn stop(&mut self) {
+        self.control.stop();
+        self.join();
+    }
+
+    fn join(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            self.searcher = Some(worker.join().expect("search thread panicked"));
+        }
+    }
+
+    /// The idle searcher, stopping the current search if there is one.
+    fn searcher(&mut self) -> &mut Searcher {
+        self.stop();
+        self.searcher.as_mut().expect("searcher is idle once the worker is joined")
+    }
+
+    /// Apply a validated option. Threads, MultiPV and Ponder need nothing
+    /// from the engine.
+    fn set_option(&mut self, option: &UciOption, value: OptionValue) {
+        match (option.name, value) {
+            (options::HASH, OptionValue::Spin(mb)) => self.searcher().tt_mut().resize(mb as usize),
+            (options::CLEAR_HASH, _) => self.searcher().tt_mut().clear(),
+            (options::MOVE_OVERHEAD, OptionValue::Spin(ms)) => {
+                self.searcher().set_move_overhead(Duration::from_millis(ms as u64))
+            }
+            _ => {}
+        }
+    }
+
+    fn go(&mut self, limits: SearchLimits, ponder: bool) {
+        self.stop();
+        let mut searcher = self.searcher.take().expect("searcher is idle once the worker is joined");
+        let mut board = self.board.clone();
+        let control = Arc::clone(&self.control);
+        control.reset(ponder);
+
+        let worker = thread::Builder::new()
+            .name("search".into())
+            .stack_size(SEARCH_STACK_SIZE)
+            .spawn(move || {
+                let info = searcher.search_with_limits(&mut board, &limits, UciReporter);
+
+                // The GUI must not get a bestmove while pondering or during an
+                // infinite search, even when the search itself has run out
+                while control.is_pondering() || (limits.infinite && !control.is_stopped()) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                print_bestmove(&info);
+                searcher
+            })
+            .expect("failed to spawn search thread");
+        self.worker = Some(worker);
+    }
+}
 
 pub fn uci_loop() {
-    let mut board = Board::startpos();
+    let mut engine = Engine::new();
 
     loop {
+        // A closed stdin means the GUI is gone
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        if !matches!(io::stdin().read_line(&mut input), Ok(n) if n > 0) {
+            engine.stop();
+            break;
+        }
         let cmd = input.trim();
+        let (command, args) = split_command(cmd);
 
         match cmd {
             "uci" => {
                 println!("id name MyChessEngine");
                 println!("id author Zac Rubin");
+                for option in OPTIONS {
+                    println!("{}", option);
+                }
                 println!("uciok");
             }
             "isready" => {
                 println!("readyok");
             }
             "ucinewgame" => {
-                board = Board::startpos();
-            }
-
-            _ if cmd.starts_with("position startpos") => {
-                board = Board::startpos();
-                
-                // Handle moves if present
-                if let Some(moves_idx) = cmd.find("moves") {
-                    let moves_str = &cmd[moves_idx + 5..].trim();
-                    let move_list: Vec<&str> = moves_str.split_whitespace().collect();
-                    
-                    for move_uci in move_list {
-                        if let Some(mv) = Move::from_uci(move_uci) {
-                            let _ = board.make_move(mv);
-                        }
-                    }
-                }
+                engine.searcher().clear();
+                engine.board = Board::startpos();
             }
 
-            _ if cmd.starts_with("go depth") => {
-                let depth: u32 = cmd.split_whitespace().last().unwrap().parse().unwrap();
-                let (_score, best) = search(&mut board, depth);
+            _ if command == "setoption" => match parse_setoption(args) {
+                Ok((option, value)) => engine.set_option(option, value),
+                Err(err) => println!("info string {}", err),
+            },
 
-                if let Some(mv) = best {
-                    println!("bestmove {}", mv.to_uci());
+            _ if command == "position" => {
+                engine.stop();
+                match parse_position(args) {
+                    Ok(board) => engine.board = board,
+                    Err(err) => println!("info string {}, position unchanged", err),
                 }
             }
 
-            "quit" => break,
+            _ if command == "go" => {
+                let ponder = args.split_whitespace().any(|token| token == "ponder");
+                engine.go(parse_go(args), ponder);
+            }
+            "stop" => engine.control.stop(),
+            "ponderhit" => engine.control.ponderhit(),
+
+            "quit" => {
+                engine.stop();
+                break;
+            }
 
             _ => {}
         }
 
         io::stdout().flush().unwrap();
     }
-}
\ No newline at end of file
+}
+
+/// Split a command line into its first token and the rest, so a command
+/// only matches as a whole word.
+fn split_command(cmd: &str) -> (&str, &str) {
+    cmd.split_once(char::is_whitespace).map_or((cmd, ""), |(command, args)| (command, args.trim_start()))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PositionError {
+    MissingPosition,
+    Fen(FenError),
+    IllegalMove(String),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PositionError::MissingPosition => write!(f, "expected startpos or fen"),
+            PositionError::Fen(err) => write!(f, "invalid fen: {}", err),
+            PositionError::IllegalMove(mv) => write!(f, "illegal move {}", mv),
+        }
+    }
+}
+
+/// Parse the arguments of `position`: `startpos` or `fen <fen>`, then
+/// optionally `moves` and the moves played from there. The moves are played
+/// through make_move so the search sees repetitions of earlier positions.
+fn parse_position(args: &str) -> Result<Board, PositionError> {
+    let (setup, moves) = args.split_once("moves").unwrap_or((args, ""));
+
+    let mut board = match setup.trim().split_once(' ') {
+        _ if setup.trim() == "startpos" => Board::startpos(),
+        Some(("fen", fen)) => Board::from_fen(fen).map_err(PositionError::Fen)?,
+        _ => return Err(PositionError::MissingPosition),
+    };
+
+    // The move string lacks capture and castling flags, so look it up among
+    // the legal moves
+    for uci in moves.split_whitespace() {
+        let legal = generate_legal_moves(&board);
+        let mv = legal
+            .iter()
+            .find(|m| m.to_uci() == uci)
+            .ok_or_else(|| PositionError::IllegalMove(uci.to_string()))?;
+        board.make_move(*mv);
+    }
+
+    Ok(board)
+}
+
+/// Parse the arguments of `go`. Unknown tokens and unparsable values are
+/// skipped rather than treated as errors.
+fn parse_go(args: &str) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut tokens = args.split_whitespace();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "infinite" => limits.infinite = true,
+            "depth" => limits.depth = next_value(&mut tokens),
+            "nodes" => limits.nodes = next_value(&mut tokens),
+            "mate" => limits.mate = next_value(&mut tokens),
+            "movestogo" => limits.movestogo = next_value(&mut tokens),
+            "movetime" => limits.movetime = next_millis(&mut tokens),
+            "wtime" => limits.wtime = next_millis(&mut tokens),
+            "btime" => limits.btime = next_millis(&mut tokens),
+            "winc" => limits.winc = next_millis(&mut tokens),
+            "binc" => limits.binc = next_millis(&mut tokens),
+            _ => {}
+        }
+    }
+
+    limits
+}
+
+fn next_value<'a, T: std::str::FromStr>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<T> {
+    tokens.next()?.parse().ok()
+}
+
+// Some GUIs send negative clock times when a player is out of time
+fn next_millis<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<Duration> {
+    let millis: i64 = next_value(tokens)?;
+    Some(Duration::from_millis(millis.max(0) as u64))
+}
+
+/// Prints search progress as UCI `info` lines.
+struct UciReporter;
+
+impl SearchReporter for UciReporter {
+    fn iteration(&mut self, info: &SearchInfo) {
+        let millis = info.time.as_millis() as u64;
+        let nps = info.nodes * 1000 / millis.max(1);
+        let pv: Vec<String> = info.pv.iter().map(|m| m.to_uci()).collect();
+        println!(
+            "info depth {} seldepth {} score {} nodes {} nps {} time {} hashfull {} pv {}",
+            info.depth,
+            info.seldepth,
+            format_score(info.score),
+            info.nodes,
+            nps,
+            millis,
+            info.hashfull,
+            pv.join(" ")
+        );
+        io::stdout().flush().unwrap();
+    }
+
+    fn root_move(&mut self, depth: u32, mv: Move, number: usize) {
+        println!("info depth {} currmove {} currmovenumber {}", depth, mv.to_uci(), number);
+        io::stdout().flush().unwrap();
+    }
+}
+
+/// Mate scores are given in moves, negative when we are the ones being mated.
+fn format_score(score: i32) -> String {
+    if !is_mate_score(score) {
+        format!("cp {}", score)
+    } else if score > 0 {
+        format!("mate {}", (MATE - score + 1) / 2)
+    } else {
+        format!("mate {}", -(MATE + score) / 2)
+    }
+}
+
+/// Every `go` is answered, with the null move when there is nothing to play.
+fn print_bestmove(info: &SearchInfo) {
+    match (info.best_move(), info.pv.get(1)) {
+        (Some(best), Some(ponder)) => println!("bestmove {} ponder {}", best.to_uci(), ponder.to_uci()),
+        (Some(best), None) => println!("bestmove {}", best.to_uci()),
+        (None, _) => println!("bestmove 0000"),
+    }
+    io::stdout().flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Option<Duration> {
+        Some(Duration::from_millis(n))
+    }
+
+    #[test]
+    fn parses_clock_limits() {
+        let limits = parse_go(" wtime 60000 btime 59000 winc 1000 binc 900 movestogo 12");
+        assert_eq!(
+            limits,
+            SearchLimits {
+                wtime: ms(60_000),
+                btime: ms(59_000),
+                winc: ms(1_000),
+                binc: ms(900),
+                movestogo: Some(12),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_fixed_limits() {
+        assert_eq!(parse_go(" depth 7").depth, Some(7));
+        assert_eq!(parse_go(" nodes 100000").nodes, Some(100_000));
+        assert_eq!(parse_go(" mate 3").mate, Some(3));
+        assert_eq!(parse_go(" movetime 250").movetime, ms(250));
+        assert!(parse_go(" infinite").infinite);
+        assert_eq!(parse_go(""), SearchLimits::default());
+    }
+
+    #[test]
+    fn tolerates_bad_input() {
+        let limits = parse_go(" depth x wtime -50 searchmoves e2e4 btime");
+        assert_eq!(limits.depth, None);
+        assert_eq!(limits.wtime, ms(0));
+        assert_eq!(limits.btime, None);
+    }
+
+    #[test]
+    fn splits_commands_on_whole_words() {
+        assert_eq!(split_command("go"), ("go", ""));
+        assert_eq!(split_command("go  depth 5"), ("go", "depth 5"));
+        assert_eq!(split_command("setoption name Hash value 1"), ("setoption", "name Hash value 1"));
+        assert_eq!(split_command("setoptionfoo name Hash value 1").0, "setoptionfoo");
+        assert_eq!(split_command("gorilla").0, "gorilla");
+    }
+
+    #[test]
+    fn parses_positions() {
+        let board = parse_position("startpos moves e2e4 e7e5 g1f3").unwrap();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(parse_position(&format!("fen {}", fen)).unwrap().to_fen(), fen);
+
+        // Castling and promotion flags are recovered from the legal moves
+        let board = parse_position(&format!("fen {} moves e1g1 h3g2 a2a3 g2f1q", fen)).unwrap();
+        assert_eq!(board.to_fen(), "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/P1N2Q2/1PPBBP1P/R4qK1 w kq - 0 3");
+    }
+
+    #[test]
+    fn rejects_bad_positions() {
+        let error = |args: &str| parse_position(args).err();
+        assert_eq!(error(""), Some(PositionError::MissingPosition));
+        assert_eq!(error("moves e2e4"), Some(PositionError::MissingPosition));
+        assert!(matches!(error("fen 8/8/8 w - - 0 1"), Some(PositionError::Fen(_))));
+        assert_eq!(error("startpos moves e2e4 e2e4"), Some(PositionError::IllegalMove("e2e4".to_string())));
+        assert_eq!(error("startpos moves e1g1"), Some(PositionError::IllegalMove("e1g1".to_string())));
+    }
+
+    #[test]
+    fn formats_scores() {
+        assert_eq!(format_score(35), "cp 35");
+        assert_eq!(format_score(-120), "cp -120");
+        assert_eq!(format_score(MATE - 1), "mate 1");
+        assert_eq!(format_score(MATE - 3), "mate 2");
+        assert_eq!(format_score(-MATE + 2), "mate -1");
+        assert_eq!(format_score(-MATE + 4), "mate -2");
+    }
+
+    #[test]
+    fn ponder_search_waits_for_ponderhit() {
+        let mut engine = Engine::new();
+        engine.go(parse_go(" ponder depth 1"), true);
+        thread::sleep(Duration::from_millis(50));
+        assert!(!engine.worker.as_ref().unwrap().is_finished());
+
+        engine.control.ponderhit();
+        engine.join();
+        assert!(engine.worker.is_none() && engine.searcher.is_some());
+    }
+
+    #[test]
+    fn stop_ends_infinite_search() {
+        let mut engine = Engine::new();
+        engine.go(parse_go(" infinite"), false);
+        thread::sleep(Duration::from_millis(50));
+        engine.stop();
+        assert!(engine.searcher.is_some());
+    }
+}